@@ -1,203 +1,4152 @@
-use std::{borrow::Cow, collections::{hash_map::Keys, HashMap}};
+use std::{borrow::Cow, collections::{HashMap, VecDeque}, fmt::{self, Write}, fs::{File, OpenOptions}, io::{LineWriter, Write as _}, path::{Path, PathBuf}, str::FromStr, sync::{Arc, Mutex}, time::Instant};
 
-use bevy::{ecs::system::SystemId, input::common_conditions::input_just_pressed, prelude::*};
-use workarounds::next_state;
+use bevy::{
+    app::AppExit,
+    diagnostic::{Diagnostic, DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    reflect::{TypeRegistration, TypeRegistry},
+    text::BreakLineOn,
+    ecs::{archetype::Archetypes, component::Components, system::{RunSystemOnce, SystemId, SystemParam}},
+    hierarchy::despawn_with_children_recursive,
+    log::{tracing_subscriber::{layer::{Context, SubscriberExt}, Layer}, BoxedSubscriber, Level},
+    prelude::*,
+    window::PrimaryWindow,
+    utils::tracing::{field::{Field, Visit}, Event as LogEvent, Subscriber},
+};
 
 #[derive(Component)]
 struct ConsoleTag;
 
+/// The text node console output is rendered into. It is rebuilt from [`ConsoleOutput`] every time that changes,
+/// so print with [`Console`] or [`ConsoleWriter`] instead of editing its [`Text`].
 #[derive(Component)]
 pub struct ConsoleOutputTag;
 
+/// The panel below the console listing commands that match what is being typed.
+#[derive(Component)]
+pub struct ConsoleSuggestionsTag;
+
 #[derive(Component)]
 struct ConsoleInputTag;
 
-#[derive(States, Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum ConsoleState {
-    #[default]
-    Closed,
-    AnimatingOpen,
-    AnimatingClosed,
-    Open,
+/// Where the console is. Only [`ConsoleState::Open`] accepts typed input, key bindings run in every other state.
+/// Use [`console_is_open`], [`console_is_animating`] and [`console_is_closed`] or `in_state` to gate your systems.
+#[derive(States, Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ConsoleState {
+    /// Hidden off screen.
+    #[default]
+    Closed,
+    /// Sliding down, not accepting input yet.
+    AnimatingOpen,
+    /// Sliding up, no longer accepting input.
+    AnimatingClosed,
+    /// Fully visible and reading the keyboard.
+    Open,
+}
+
+/// Console settings. Insert your own before adding [`ConsolePlugin`], or set them through its builder methods,
+/// to override the defaults.
+#[derive(Resource, Debug, Clone)]
+pub struct ConsoleConfig {
+    /// Key that opens and closes the console. Defaults to [`KeyCode::Backquote`].
+    pub toggle_key: KeyCode,
+    /// Console height as a percentage of the window height, or its width for [`ConsoleAnchor::Left`] and
+    /// [`ConsoleAnchor::Right`]. Defaults to `33.3`.
+    pub height_percent: f32,
+    /// Window edge the console slides in from. Defaults to [`ConsoleAnchor::Top`].
+    pub anchor: ConsoleAnchor,
+    /// Open/close animation speed in percent of the window height per second, on average. Defaults to `100.0`.
+    pub move_speed: f32,
+    /// Speed curve of the open/close animation. Defaults to [`ConsoleEasing::EaseInOut`].
+    pub easing: ConsoleEasing,
+    /// When set, keyboard input is swallowed while the console is open so gameplay systems ordered
+    /// `.after(ConsoleSet)` don't react to typing: keys held when it opens are released and presses
+    /// and releases are hidden from then on. Defaults to `false`.
+    pub capture_input: bool,
+    /// Match command names and aliases ignoring ASCII case. `help` still shows them as registered. Defaults to `false`.
+    pub case_insensitive: bool,
+    /// Least severe log level shown in the console when log capture is installed with [`capture_log`]. Defaults to [`Level::INFO`].
+    pub log_level: Level,
+    /// Save [`CommandHistory`] to [`ConsoleConfig::history_file`] on exit and load it back on startup. Defaults to `false`.
+    pub persist_history: bool,
+    /// Where history is saved. Defaults to `console_history.txt` in a per-executable folder of the OS data directory.
+    pub history_file: PathBuf,
+    /// Seconds the input cursor stays shown, then hidden, while blinking. `0.0` keeps it always shown. Defaults to `0.5`.
+    pub cursor_blink_interval: f32,
+    /// Most command names shown in the suggestion panel while typing, followed by a `+N more` row if more match.
+    /// Tab lists every match then rather than picking from the panel. `0` disables the panel. Defaults to `8`.
+    pub max_suggestions: usize,
+    /// Print each entered line to the output before running it. Defaults to `true`.
+    pub echo_input: bool,
+    /// Print how long each command took to run, e.g. `(took 12.3ms)`. Defaults to `false`.
+    pub profile: bool,
+    /// Most characters the input line accepts, whether typed, pasted, completed or recalled from history.
+    /// `None` means unlimited. Defaults to `None`.
+    pub max_input_len: Option<usize>,
+    /// Prefix output lines with the time they were printed, e.g. `[12.34] spawned enemy`. Defaults to `false`.
+    pub timestamps: bool,
+    /// Pause [`Time<Virtual>`] while the console is open. A game that was already paused stays paused when
+    /// the console closes. Defaults to `false`.
+    pub pause_on_open: bool,
+    /// Seconds Backspace, Delete and the arrow keys have to be held before they repeat. Defaults to `0.5`.
+    pub key_repeat_delay: f32,
+    /// Seconds between repeats of a held key. `0.0` disables repeating. Defaults to `0.03`.
+    pub key_repeat_interval: f32,
+    /// Most matches Tab lists when it can't complete any further. The rest are summed up as `...and N more`.
+    /// `0` means unlimited. Defaults to `20`.
+    pub max_completions: usize,
+    /// Name and version of your game, e.g. `MyGame 1.4.2`, printed by the `version` command after the console's own. Defaults to `None`.
+    pub game_version: Option<String>,
+    /// Text shown before the input line, e.g. `"game$ "`. Can be changed while the game runs. Defaults to `" > "`.
+    pub prompt: Cow<'static, str>,
+    /// Output lines scrolled per mouse wheel notch while the cursor is over the output. Defaults to `3`.
+    pub wheel_scroll_lines: usize,
+    /// Save [`Cvars`] to [`ConsoleConfig::cvars_file`] on exit and load them back before `Startup` systems run.
+    /// Defaults to `false`.
+    pub persist_cvars: bool,
+    /// Where cvars are saved, as `set <name> <value>` lines. Defaults to `config.cfg` next to the history file.
+    pub cvars_file: PathBuf,
+    /// Most queued commands run per frame, the rest wait for the next ones so a long script can't stall a frame.
+    /// `0` counts as `1`. Defaults to `1`.
+    pub max_commands_per_frame: usize,
+    /// Close the console when Enter submits a line. Shift+Enter keeps it open either way. Defaults to `false`.
+    pub close_on_submit: bool,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> Self {
+        Self { toggle_key: KeyCode::Backquote, height_percent: 33.3, anchor: ConsoleAnchor::Top, move_speed: 100.0, easing: ConsoleEasing::EaseInOut, capture_input: false, case_insensitive: false, log_level: Level::INFO,
+            persist_history: false, history_file: default_data_file("console_history.txt"), cursor_blink_interval: 0.5,
+            max_suggestions: 8, echo_input: true, profile: false, max_input_len: None, timestamps: false,
+            pause_on_open: false, key_repeat_delay: 0.5, key_repeat_interval: 0.03,
+            max_completions: 20, game_version: None, prompt: Cow::Borrowed(" > "),
+            wheel_scroll_lines: 3, persist_cvars: false, cvars_file: default_data_file("config.cfg"),
+            max_commands_per_frame: 1, close_on_submit: false }
+    }
+}
+
+/// Window edge the console is attached to and slides in from.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ConsoleAnchor {
+    #[default]
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl ConsoleAnchor {
+    /// The style offset that is animated - the distance of the console from its edge.
+    fn offset(self, style:&mut Style) -> &mut Val {
+        match self {
+            Self::Top => &mut style.top,
+            Self::Bottom => &mut style.bottom,
+            Self::Left => &mut style.left,
+            Self::Right => &mut style.right,
+        }
+    }
+
+    fn is_horizontal(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+}
+
+/// How the console speeds up and slows down while sliding, see [`ConsoleConfig::easing`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ConsoleEasing {
+    /// Constant speed.
+    Linear,
+    /// Starts slow and ends at full speed.
+    EaseIn,
+    /// Starts at full speed and slows down.
+    EaseOut,
+    /// Slow at both ends.
+    #[default]
+    EaseInOut,
+}
+
+impl ConsoleEasing {
+    /// Maps animation progress from `0.0` (closed) to `1.0` (open) to how far open the console is shown.
+    pub fn ease(self, progress:f32) -> f32 {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// `<data dir>/<executable name>/<name>`, or just `name` if the data directory is unknown.
+fn default_data_file(name:&str) -> PathBuf {
+    let app = std::env::current_exe().ok().and_then(|exe| exe.file_stem().map(PathBuf::from));
+    match (data_dir(), app) {
+        (Some(dir), Some(app)) => dir.join(app).join(name),
+        _ => PathBuf::from(name),
+    }
+}
+
+fn data_dir() -> Option<PathBuf> {
+    let env = |name| std::env::var_os(name).filter(|dir| !dir.is_empty()).map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        env("XDG_DATA_HOME").or_else(|| env("HOME").map(|home| home.join(".local/share")))
+    }
+}
+
+/// Systems reading the keyboard for the console. Order your gameplay input systems after it when using [`ConsoleConfig::capture_input`].
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConsoleSet;
+
+/// Run condition that is true while the console is fully closed. Gate your gameplay input with it so typing
+/// in the console doesn't move your character:
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// # let mut app = App::new();
+/// # fn player_movement() {}
+/// app.add_systems(Update, player_movement.run_if(console_closed()));
+/// ```
+pub fn console_closed() -> impl FnMut(Option<Res<State<ConsoleState>>>) -> bool + Clone {
+    console_is_closed()
+}
+
+/// Run condition that is true while the console is fully closed, same as [`console_closed`].
+pub fn console_is_closed() -> impl FnMut(Option<Res<State<ConsoleState>>>) -> bool + Clone {
+    in_state(ConsoleState::Closed)
+}
+
+/// Run condition that is true while the console is fully open and accepting input.
+pub fn console_is_open() -> impl FnMut(Option<Res<State<ConsoleState>>>) -> bool + Clone {
+    in_state(ConsoleState::Open)
+}
+
+/// Run condition that is true while the console slides open or closed.
+pub fn console_is_animating() -> impl FnMut(Option<Res<State<ConsoleState>>>) -> bool + Clone {
+    |state:Option<Res<State<ConsoleState>>>| state.is_some_and(|state| matches!(state.get(), ConsoleState::AnimatingOpen | ConsoleState::AnimatingClosed))
+}
+
+/// Run condition that is true while the console `id` is the active one, shown and typed into. See [`ConsolePlugin::new`].
+pub fn console_is_active(id:impl Into<Cow<'static, str>>) -> impl FnMut(Option<Res<ConsoleInstances>>) -> bool + Clone {
+    let id = id.into();
+    move |instances:Option<Res<ConsoleInstances>>| instances.is_some_and(|instances| instances.active == id)
+}
+
+/// Run condition that is true while the console `id` is active and fully open:
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// # let mut app = App::new();
+/// # fn show_server_stats() {}
+/// app.add_systems(Update, show_server_stats.run_if(console_instance_is_open("server")));
+/// ```
+pub fn console_instance_is_open(id:impl Into<Cow<'static, str>>) -> impl FnMut(Option<Res<ConsoleInstances>>, Option<Res<State<ConsoleState>>>) -> bool + Clone {
+    let mut active = console_is_active(id);
+    move |instances:Option<Res<ConsoleInstances>>, state:Option<Res<State<ConsoleState>>>| {
+        active(instances) && state.is_some_and(|state| *state.get() == ConsoleState::Open)
+    }
+}
+
+fn capture_input_enabled(config:Res<ConsoleConfig>) -> bool {
+    config.capture_input
+}
+
+/// Hides this frame's presses and releases from systems running after [`ConsoleSet`]. Which keys are held is kept,
+/// the console reads it next frame for modifiers like Ctrl and Shift and for key repeat.
+fn capture_keyboard(mut kbd:ResMut<ButtonInput<KeyCode>>) {
+    kbd.clear();
+}
+
+/// Releases the keys held while the console opened, e.g. a movement key, so gameplay doesn't see them stuck.
+fn release_keyboard(mut kbd:ResMut<ButtonInput<KeyCode>>) {
+    kbd.reset_all();
+}
+
+/// Starts opening the console. Does nothing unless it is fully closed. Add it to your schedule with a run condition
+/// or register it with [`World::register_system`] to open the console from a button:
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// # let mut app = App::new();
+/// # fn pause_pressed() -> bool { false }
+/// app.add_systems(Update, open_console.run_if(pause_pressed));
+/// ```
+pub fn open_console(state:Res<State<ConsoleState>>, mut next:ResMut<NextState<ConsoleState>>) {
+    if *state.get() == ConsoleState::Closed {
+        next.set(ConsoleState::AnimatingOpen);
+    }
+}
+
+/// Starts closing the console. Does nothing unless it is fully open.
+pub fn close_console(state:Res<State<ConsoleState>>, mut next:ResMut<NextState<ConsoleState>>) {
+    if *state.get() == ConsoleState::Open {
+        next.set(ConsoleState::AnimatingClosed);
+    }
+}
+
+/// Opens a closed console or closes an open one, same as pressing [`ConsoleConfig::toggle_key`].
+/// Does nothing while the console is animating.
+pub fn toggle_console(state:Res<State<ConsoleState>>, mut next:ResMut<NextState<ConsoleState>>) {
+    match state.get() {
+        ConsoleState::Closed => next.set(ConsoleState::AnimatingOpen),
+        ConsoleState::Open => next.set(ConsoleState::AnimatingClosed),
+        ConsoleState::AnimatingOpen | ConsoleState::AnimatingClosed => (),
+    }
+}
+
+/// Whether virtual time was already paused when the console paused it, `None` if the console didn't pause it.
+#[derive(Resource, Default)]
+struct PausedByConsole(Option<bool>);
+
+fn pause_time(config:Res<ConsoleConfig>, mut time:ResMut<Time<Virtual>>, mut paused:ResMut<PausedByConsole>) {
+    if config.pause_on_open {
+        paused.0 = Some(time.is_paused());
+        time.pause();
+    }
+}
+
+fn restore_time(mut time:ResMut<Time<Virtual>>, mut paused:ResMut<PausedByConsole>) {
+    if paused.0.take() == Some(false) {
+        time.unpause();
+    }
+}
+
+/// Swallows the toggle keypress - both the key edge and its character - so neither gameplay systems nor the
+/// input line see it, whichever way the console is going.
+fn consume_toggle_key(config:Res<ConsoleConfig>, mut kbd:ResMut<ButtonInput<KeyCode>>, mut evts:ResMut<Events<ReceivedCharacter>>) {
+    kbd.clear_just_pressed(config.toggle_key);
+    evts.clear();
+}
+
+fn toggle_key_pressed(config:Res<ConsoleConfig>, kbd:Res<ButtonInput<KeyCode>>, input:Res<InputLine>) -> bool {
+    // The key may be part of an IME composition
+    kbd.just_pressed(config.toggle_key) && input.preedit.is_empty()
+}
+
+/// How far the console has slid open, from `0.0` (closed) to `1.0` (open), before easing.
+/// Advanced while the console is animating and applied to the console's [`Style`] by a separate system,
+/// so the animation also runs, and can be inspected, without any UI.
+#[derive(Resource, Debug, Default, Copy, Clone, PartialEq)]
+pub struct ConsoleAnimation {
+    pub progress: f32,
+}
+
+fn animate_console(
+    time:Res<Time<Real>>,
+    config:Res<ConsoleConfig>,
+    state:Res<State<ConsoleState>>,
+    mut animation:ResMut<ConsoleAnimation>,
+    mut next:ResMut<NextState<ConsoleState>>,
+    mut evts:ResMut<Events<ReceivedCharacter>>,
+) {
+    // Drop everything typed while sliding every frame, the toggle key's own character included even when
+    // it arrives a frame late, so the input line starts with exactly what is typed once it's open.
+    evts.clear();
+    // The whole slide takes `height_percent / move_speed` seconds whatever the easing.
+    let step = config.move_speed / config.height_percent.max(f32::EPSILON) * time.delta_seconds();
+    animation.progress = match **state {
+        ConsoleState::AnimatingOpen => animation.progress + step,
+        ConsoleState::AnimatingClosed => animation.progress - step,
+        _ => animation.progress,
+    }.clamp(0.0, 1.0);
+
+    match **state {
+        ConsoleState::AnimatingClosed if animation.progress <= 0.0 => next.set(ConsoleState::Closed),
+        ConsoleState::AnimatingOpen if animation.progress >= 1.0 => next.set(ConsoleState::Open),
+        _ => ()
+    }
+}
+
+fn apply_animation(
+    config:Res<ConsoleConfig>,
+    animation:Res<ConsoleAnimation>,
+    mut style:Query<&mut Style, With<ConsoleTag>>,
+    mut warned:Local<bool>,
+) {
+    let Ok(mut style) = style.get_single_mut() else {
+        if !*warned {
+            warn!("Console UI not found, the console will open and close without showing");
+            *warned = true;
+        }
+        return;
+    };
+    let offset = -config.height_percent * (1.0 - config.easing.ease(animation.progress));
+    *config.anchor.offset(&mut style) = Val::Percent(offset);
+}
+
+/// Console colors and font. Insert your own to restyle the console; changes are applied live.
+#[derive(Resource, Debug, Clone)]
+pub struct ConsoleTheme {
+    /// Fill behind the console and the suggestion panel. Its alpha is blended, so e.g.
+    /// `Color::rgba(0., 0., 0., 0.7)` lets the game show through. Defaults to opaque black.
+    pub background:Color,
+    /// Color of output lines printed without an explicit color.
+    pub output_color:Color,
+    pub input_color:Color,
+    /// Color of the command name in the input line when it names a command or alias.
+    pub known_command_color:Color,
+    /// Color of the command name in the input line when no such command exists.
+    pub unknown_command_color:Color,
+    /// Color of output lines selected with the mouse.
+    pub selection_color:Color,
+    /// Character drawn at the input cursor, e.g. `'█'` or `'_'`. `None` hides the cursor. Defaults to `'|'`.
+    pub cursor_char:Option<char>,
+    /// Bevy's default font isn't monospace, so use a monospaced one to get aligned columns from [`ConsoleWriter::write_table`],
+    /// which `help` and `binds` print with.
+    pub font:Handle<Font>,
+    pub font_size:f32,
+}
+
+impl Default for ConsoleTheme {
+    fn default() -> Self {
+        let text = TextStyle::default();
+        Self { background:Color::BLACK, output_color:text.color, input_color:text.color, known_command_color:Color::GREEN, unknown_command_color:Color::RED,
+            selection_color:Color::CYAN, cursor_char:Some('|'), font:text.font, font_size:text.font_size }
+    }
+}
+
+impl ConsoleTheme {
+    fn cursor_text(&self) -> String {
+        self.cursor_char.map(String::from).unwrap_or_default()
+    }
+
+    fn text_style(&self, color:Color) -> TextStyle {
+        TextStyle { font:self.font.clone(), font_size:self.font_size, color }
+    }
+}
+
+type ThemedBackground = Or<(With<ConsoleTag>, With<ConsoleSuggestionsTag>)>;
+
+/// Restyles the console UI whenever [`ConsoleTheme`] changes.
+fn apply_theme(
+    theme:Res<ConsoleTheme>,
+    mut output:ResMut<ConsoleOutput>,
+    mut background:Query<&mut BackgroundColor, ThemedBackground>,
+    mut input_field:Query<&mut Text, (With<ConsoleInputTag>, Without<ConsoleOutputTag>)>,
+    mut suggestions:ResMut<Suggestions>,
+) {
+    for mut background in background.iter_mut() {
+        background.0 = theme.background;
+    }
+    if let Ok(mut text) = input_field.get_single_mut() {
+        for section in text.sections.iter_mut() {
+            section.style = theme.text_style(theme.input_color);
+        }
+        text.sections[INPUT_CURSOR].value = theme.cursor_text();
+    }
+    // Output and suggestion sections are rebuilt from the theme on every render.
+    output.set_changed();
+    suggestions.set_changed();
+}
+
+fn setup_ui(mut commands:Commands, config:Res<ConsoleConfig>, theme:Res<ConsoleTheme>) {
+    let size = Val::Percent(config.height_percent);
+    let input_style = theme.text_style(theme.input_color);
+    let mut style = Style{
+        position_type:PositionType::Absolute,
+        display:Display::Flex,
+        flex_direction:FlexDirection::Column,
+        padding:UiRect::px(12., 12., 12., 0.0),
+        ..default()
+    };
+    if config.anchor.is_horizontal() {
+        (style.width, style.min_width, style.max_width, style.height) = (size, size, size, Val::Percent(100.));
+    } else {
+        (style.height, style.min_height, style.max_height, style.width) = (size, size, size, Val::Percent(100.));
+    }
+    *config.anchor.offset(&mut style) = Val::Percent(-config.height_percent);
+    // The suggestion panel hangs off the side of the console facing the middle of the window.
+    let (panel_top, panel_bottom, panel_left, panel_right) = match config.anchor {
+        ConsoleAnchor::Top => (Val::Percent(100.), Val::Auto, Val::Px(12.), Val::Auto),
+        ConsoleAnchor::Bottom => (Val::Auto, Val::Percent(100.), Val::Px(12.), Val::Auto),
+        ConsoleAnchor::Left => (Val::Auto, Val::Px(0.), Val::Percent(100.), Val::Auto),
+        ConsoleAnchor::Right => (Val::Auto, Val::Px(0.), Val::Auto, Val::Percent(100.)),
+    };
+    commands.spawn((ConsoleTag, NodeBundle{style,
+        background_color:BackgroundColor(theme.background),
+        ..default()
+    })).with_children(|console|{
+        console.spawn((ConsoleOutputTag, TextBundle{style:Style{width:Val::Percent(100.), height:Val::Percent(80.), min_height:Val::Percent(80.), ..default()}, text:Text {
+            // Breaking anywhere keeps the wrapped rows in step with `ConsoleOutput::rows`.
+            linebreak_behavior:BreakLineOn::AnyCharacter,
+            ..Text::from_section("" , theme.text_style(theme.output_color))
+        }, ..default()}));
+        // See the INPUT_* section indices
+        console.spawn((ConsoleInputTag, TextBundle{style:Style{width:Val::Percent(100.), ..default()}, text:Text::from_sections(vec![
+            TextSection::new(config.prompt.clone(), input_style.clone()),
+            TextSection::from_style(input_style.clone()),
+            TextSection::from_style(input_style.clone()),
+            TextSection::new(theme.cursor_text(), input_style.clone()),
+            TextSection::from_style(input_style.clone()),
+            TextSection::from_style(input_style),
+        ]), ..default()}));
+        console.spawn((ConsoleSuggestionsTag, TextBundle{style:Style{
+                display:Display::None,
+                position_type:PositionType::Absolute,
+                top:panel_top,
+                bottom:panel_bottom,
+                left:panel_left,
+                right:panel_right,
+                padding:UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            background_color:BackgroundColor(theme.background),
+            ..default()
+        }));
+    });
+}
+
+/// A resource modified before the system call to include command line arguments meant for that system.
+#[derive(Resource, Deref)]
+pub struct CommandArgs {
+    #[deref]
+    raw:String,
+    tokens:Vec<String>,
+    name:String,
+    invoked_as:String,
+}
+
+impl CommandArgs {
+    fn new(raw:String) -> Self {
+        let tokens = tokenize(&raw);
+        let name = tokens.first().cloned().unwrap_or_default();
+        Self { invoked_as:name.clone(), name, tokens, raw }
+    }
+
+    /// Runs `line` instead, e.g. an alias' target, remembering the name the command was invoked as.
+    fn replace(&mut self, line:String) {
+        let invoked_as = std::mem::take(&mut self.invoked_as);
+        *self = Self { invoked_as, ..Self::new(line) };
+    }
+
+    /// Name of the running command as it was registered, so one system added under several names can tell them apart.
+    /// Aliases are expanded first, so this is the command an alias points to - [`CommandArgs::invoked_as`] has the alias.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name the command was typed as - the alias when it was run through one, see [`ConsolePlugin::add_alias`].
+    /// Otherwise the same as [`CommandArgs::name`], except for its case when [`ConsoleConfig::case_insensitive`] is set.
+    pub fn invoked_as(&self) -> &str {
+        &self.invoked_as
+    }
+
+    /// The line exactly as it was entered, command name included.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Arguments with the command name stripped, split as described in [`tokenize`].
+    /// Quoted arguments are kept whole, so `spawn "my entity"` yields `["my entity"]`.
+    pub fn args(&self) -> Vec<&str> {
+        self.tokens.iter().skip(1).map(String::as_str).collect()
+    }
+
+    /// Parses the argument at `index` (0 is the first argument after the command name).
+    pub fn parse<T>(&self, index:usize) -> Result<T, ArgError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        parse_arg(&self.args(), index)
+    }
+
+    /// Parses the leading arguments into a tuple, e.g. `args.parse_args::<(f32, f32, String)>()`.
+    /// Extra arguments are ignored.
+    pub fn parse_args<T:FromArgs>(&self) -> Result<T, ArgError> {
+        T::from_args(&self.args())
+    }
+}
+
+/// Why an argument couldn't be extracted from [`CommandArgs`]. Indices are 0-based and don't count the command name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    Missing { index:usize },
+    Invalid { index:usize, value:String, reason:String },
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgError::Missing { index } => write!(f, "missing argument {}", index + 1),
+            ArgError::Invalid { index, value, reason } => write!(f, "argument {} ({value:?}) is invalid: {reason}", index + 1),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+fn parse_arg<T>(args:&[&str], index:usize) -> Result<T, ArgError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = args.get(index).ok_or(ArgError::Missing { index })?;
+    value.parse().map_err(|err:T::Err| ArgError::Invalid { index, value:value.to_string(), reason:err.to_string() })
+}
+
+/// Tuples of [`FromStr`] types that can be parsed out of command arguments, see [`CommandArgs::parse_args`].
+pub trait FromArgs: Sized {
+    fn from_args(args:&[&str]) -> Result<Self, ArgError>;
+}
+
+macro_rules! impl_from_args {
+    ($($idx:tt $t:ident),+) => {
+        impl<$($t),+> FromArgs for ($($t,)+)
+        where
+            $($t: FromStr, $t::Err: fmt::Display),+
+        {
+            fn from_args(args:&[&str]) -> Result<Self, ArgError> {
+                Ok(($(parse_arg::<$t>(args, $idx)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_args!(0 A);
+impl_from_args!(0 A, 1 B);
+impl_from_args!(0 A, 1 B, 2 C);
+impl_from_args!(0 A, 1 B, 2 C, 3 D);
+impl_from_args!(0 A, 1 B, 2 C, 3 D, 4 E);
+impl_from_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+impl_from_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+impl_from_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+
+/// A typed command argument that parses itself and suggests values for Tab completion,
+/// see [`ConsolePlugin::add_cmd_typed`]. Implemented for the primitive types and [`String`].
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// struct EntityName(String);
+///
+/// impl ConsoleArg for EntityName {
+///     fn parse(arg:&str) -> Result<Self, String> {
+///         Ok(Self(arg.to_owned()))
+///     }
+///
+///     fn complete(_partial:&str, world:&World) -> Vec<String> {
+///         world.iter_entities().filter_map(|entity| entity.get::<Name>()).map(|name| name.to_string()).collect()
+///     }
+/// }
+/// ```
+pub trait ConsoleArg: Sized {
+    fn parse(arg:&str) -> Result<Self, String>;
+
+    /// Candidates for an argument of this type while `partial` of it is typed. They are filtered by `partial`
+    /// afterwards, so every valid value may be returned. Suggests nothing by default.
+    fn complete(_partial:&str, _world:&World) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+macro_rules! impl_console_arg {
+    ($($t:ty),+) => {
+        $(impl ConsoleArg for $t {
+            fn parse(arg:&str) -> Result<Self, String> {
+                arg.parse().map_err(|err:<$t as FromStr>::Err| err.to_string())
+            }
+        })+
+    };
+}
+
+impl_console_arg!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, char, String);
+
+impl ConsoleArg for bool {
+    fn parse(arg:&str) -> Result<Self, String> {
+        arg.parse().map_err(|err:std::str::ParseBoolError| err.to_string())
+    }
+
+    fn complete(_partial:&str, _world:&World) -> Vec<String> {
+        vec!["true".into(), "false".into()]
+    }
+}
+
+/// Tuples of [`ConsoleArg`] types a typed command takes, see [`ConsolePlugin::add_cmd_typed`].
+/// Extra arguments are ignored, like with [`FromArgs`].
+pub trait ConsoleArgs: Sized {
+    fn parse_args(args:&[&str]) -> Result<Self, ArgError>;
+
+    /// Candidates for the argument at `index` (0 is the first argument after the command name).
+    fn complete_arg(index:usize, partial:&str, world:&World) -> Vec<String>;
+}
+
+fn parse_console_arg<T:ConsoleArg>(args:&[&str], index:usize) -> Result<T, ArgError> {
+    let value = args.get(index).ok_or(ArgError::Missing { index })?;
+    T::parse(value).map_err(|reason| ArgError::Invalid { index, value:value.to_string(), reason })
+}
+
+macro_rules! impl_console_args {
+    ($($idx:tt $t:ident),+) => {
+        impl<$($t:ConsoleArg),+> ConsoleArgs for ($($t,)+) {
+            fn parse_args(args:&[&str]) -> Result<Self, ArgError> {
+                Ok(($(parse_console_arg::<$t>(args, $idx)?,)+))
+            }
+
+            fn complete_arg(index:usize, partial:&str, world:&World) -> Vec<String> {
+                match index {
+                    $($idx => $t::complete(partial, world),)+
+                    _ => Vec::new(),
+                }
+            }
+        }
+    };
+}
+
+impl_console_args!(0 A);
+impl_console_args!(0 A, 1 B);
+impl_console_args!(0 A, 1 B, 2 C);
+impl_console_args!(0 A, 1 B, 2 C, 3 D);
+impl_console_args!(0 A, 1 B, 2 C, 3 D, 4 E);
+impl_console_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+impl_console_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+impl_console_args!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+
+/// Splits a command line into words on whitespace.
+/// Text inside `"` or `'` quotes stays in one word and a backslash makes the next character literal,
+/// so `say "hello world" it\'s` yields `["say", "hello world", "it's"]`.
+/// An unterminated quote runs to the end of the line and `""` yields an empty word.
+pub fn tokenize(input:&str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    // Tracked separately from `token.is_empty()` so that `""` still produces a word.
+    let mut in_token = false;
+    let mut quote = None;
+    let mut chars = input.chars();
+    while let Some(char) = chars.next() {
+        match (char, quote) {
+            ('\\', _) => {
+                token.push(chars.next().unwrap_or('\\'));
+                in_token = true;
+            },
+            (char, Some(open)) if char == open => quote = None,
+            (char, Some(_)) => token.push(char),
+            ('"' | '\'', None) => {
+                quote = Some(char);
+                in_token = true;
+            },
+            (char, None) if char.is_whitespace() => if in_token {
+                tokens.push(std::mem::take(&mut token));
+                in_token = false;
+            },
+            (char, None) => {
+                token.push(char);
+                in_token = true;
+            },
+        }
+    }
+    if in_token {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Splits `line` into its first word and the rest.
+fn split_name(line:&str) -> (&str, &str) {
+    let line = line.trim_start();
+    line.split_once(char::is_whitespace).unwrap_or((line, ""))
+}
+
+/// One line of console output.
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleLine {
+    pub text:String,
+    /// `None` uses the console's regular text color.
+    pub color:Option<Color>,
+    /// Real time in seconds since startup when the line was started, shown with [`ConsoleConfig::timestamps`].
+    pub time:f32,
+}
+
+/// Backbuffer of everything printed to the console. Only the lines that fit on screen are rendered.
+#[derive(Resource)]
+pub struct ConsoleOutput {
+    /// Never empty - the last entry is the line currently being written to.
+    lines:VecDeque<ConsoleLine>,
+    max_lines:usize,
+    /// How many lines the view is scrolled up from the bottom.
+    scroll:usize,
+    /// Whether the view follows new output. Cleared by scrolling up, set again once scrolled back to the bottom.
+    sticky:bool,
+    /// How many rows fit in the output area, updated when rendering.
+    visible:usize,
+    /// How many characters fit on one row, updated when rendering. Longer lines wrap onto more rows. `0` until laid out.
+    columns:usize,
+    /// Whether lines are shown with a timestamp, which takes up room on their first row.
+    timestamps:bool,
+    /// Lines selected with the mouse, as indices into `lines` - the one clicked first and the one dragged to.
+    selection:Option<(usize, usize)>,
+    /// Time new lines are stamped with, updated every frame.
+    now:f32,
+    /// How many lines were dropped from the front so far, so line numbers don't change as old lines go.
+    dropped:usize,
+}
+
+impl Default for ConsoleOutput {
+    fn default() -> Self {
+        Self::with_max_lines(1000)
+    }
+}
+
+impl ConsoleOutput {
+    /// An empty backbuffer holding at most `max_lines` lines.
+    pub fn with_max_lines(max_lines:usize) -> Self {
+        Self { lines:VecDeque::from([ConsoleLine::default()]), max_lines:max_lines.max(1), scroll:0, sticky:true, visible:1, columns:0, timestamps:false,
+            selection:None, now:0.0, dropped:0 }
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(|line| line.text.as_str())
+    }
+
+    /// Lines with their number, counted from 1 for the first line ever printed - lines dropped from
+    /// the backbuffer or cleared keep their numbers taken.
+    pub fn numbered_lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.lines().enumerate().map(|(index, line)| (self.dropped + index + 1, line))
+    }
+
+    pub fn styled_lines(&self) -> impl Iterator<Item = &ConsoleLine> {
+        self.lines.iter()
+    }
+
+    pub fn max_lines(&self) -> usize {
+        self.max_lines
+    }
+
+    /// Changes the backbuffer size, dropping the oldest lines if it shrinks.
+    pub fn set_max_lines(&mut self, max_lines:usize) {
+        self.max_lines = max_lines.max(1);
+        self.truncate();
+    }
+
+    /// Drops every line from the backbuffer.
+    pub fn clear(&mut self) {
+        self.dropped += self.lines.len();
+        self.lines.clear();
+        self.lines.push_back(ConsoleLine::default());
+        self.scroll = 0;
+        self.sticky = true;
+        self.selection = None;
+    }
+
+    /// True while the view is at the bottom and follows new output.
+    pub fn is_at_bottom(&self) -> bool {
+        self.sticky
+    }
+
+    /// Appends `text`, starting a new line on every `\n`. A line takes the color of the text that started it.
+    fn push_str(&mut self, text:&str, color:Option<Color>) {
+        let mut parts = text.split('\n');
+        if let (Some(first), Some(line)) = (parts.next(), self.lines.back_mut()) {
+            if line.text.is_empty() {
+                line.color = color;
+                line.time = self.now;
+            }
+            line.text.push_str(first);
+        }
+        for part in parts {
+            self.lines.push_back(ConsoleLine { text:part.to_owned(), color, time:self.now });
+            // Keep the view on the same lines when scrolled up.
+            if !self.sticky {
+                self.scroll += 1;
+            }
+        }
+        self.truncate();
+    }
+
+    /// Where the next text goes, as the number of the line being written to and how many bytes it holds so far.
+    fn end(&self) -> (usize, usize) {
+        (self.dropped + self.lines.len() - 1, self.lines.back().map_or(0, |line| line.text.len()))
+    }
+
+    /// Text pushed since `end` was taken, leaving out lines already dropped again.
+    fn text_since(&self, (start, len):(usize, usize)) -> String {
+        self.lines.iter().zip(self.dropped..)
+            .filter(|(_, number)| *number >= start)
+            .map(|(line, number)| if number == start { line.text.get(len..).unwrap_or_default() } else { &line.text })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn truncate(&mut self) {
+        while self.lines.len() > self.max_lines {
+            self.lines.pop_front();
+            self.dropped += 1;
+            self.selection = self.selection.and_then(|(from, to)| Some((from.checked_sub(1)?, to.checked_sub(1)?)));
+        }
+        self.clamp_scroll();
+    }
+
+    fn clamp_scroll(&mut self) {
+        self.scroll = self.scroll.min(self.max_scroll());
+        if self.scroll == 0 {
+            self.sticky = true;
+        }
+    }
+
+    /// Rows `line` takes up on screen once wrapped.
+    fn rows(&self, line:&ConsoleLine) -> usize {
+        if self.columns == 0 {
+            return 1;
+        }
+        let mut chars = line.text.chars().count();
+        if self.timestamps {
+            chars += timestamp(line.time).chars().count();
+        }
+        chars.div_ceil(self.columns).max(1)
+    }
+
+    /// How many of `lines`, taken in order, fit on screen. Always at least one.
+    fn fitting<'a>(&self, lines:impl Iterator<Item = &'a ConsoleLine>) -> usize {
+        let mut rows = 0;
+        lines.take_while(|line| {
+            rows += self.rows(line);
+            rows <= self.visible
+        }).count().max(1)
+    }
+
+    /// Indices of the lines currently on screen.
+    fn visible_range(&self) -> std::ops::Range<usize> {
+        let end = self.lines.len() - self.scroll;
+        end.saturating_sub(self.fitting(self.lines.range(..end).rev()))..end
+    }
+
+    /// Index of the line shown on screen row `row`, or of the last line shown if `row` is below them.
+    fn line_at_row(&self, row:usize) -> usize {
+        let range = self.visible_range();
+        let mut bottom = 0;
+        for idx in range.clone() {
+            bottom += self.rows(&self.lines[idx]);
+            if row < bottom {
+                return idx;
+            }
+        }
+        range.end.saturating_sub(1)
+    }
+
+    fn selected_range(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        self.selection.map(|(from, to)| from.min(to)..=from.max(to))
+    }
+
+    /// The text of the lines selected with the mouse.
+    fn selected_text(&self) -> Option<String> {
+        let range = self.selected_range()?;
+        Some(self.lines.range(range).map(|line| line.text.as_str()).collect::<Vec<_>>().join("\n"))
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(self.fitting(self.lines.iter()))
+    }
+
+    fn scroll_up(&mut self) {
+        self.scroll = (self.scroll + self.visible.saturating_sub(1).max(1)).min(self.max_scroll());
+        self.sticky = self.scroll == 0;
+    }
+
+    fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(self.visible.saturating_sub(1).max(1));
+        self.sticky = self.scroll == 0;
+    }
+
+    /// Scrolls `lines` lines up, or down if negative.
+    fn scroll_by(&mut self, lines:isize) {
+        self.scroll = self.scroll.saturating_add_signed(lines).min(self.max_scroll());
+        self.sticky = self.scroll == 0;
+    }
+}
+
+/// Sent with everything printed through [`ConsoleWriter`], e.g. to forward the output of a
+/// [`ConsolePlugin::headless`] console. `text` includes its line breaks.
+#[derive(Event, Debug, Clone)]
+pub struct ConsolePrinted {
+    pub text:String,
+    /// `None` for text printed without an explicit color.
+    pub color:Option<Color>,
+}
+
+/// Appends text to the console output. Add it to a command system's parameters to print from it.
+#[derive(SystemParam)]
+pub struct ConsoleWriter<'w> {
+    output:ResMut<'w, ConsoleOutput>,
+    file_log:ResMut<'w, ConsoleFileLog>,
+    printed:EventWriter<'w, ConsolePrinted>,
+}
+
+impl ConsoleWriter<'_> {
+    fn push_str(&mut self, text:&str, color:Option<Color>) {
+        self.output.push_str(text, color);
+        self.file_log.write(text);
+        self.printed.send(ConsolePrinted { text:text.to_owned(), color });
+    }
+
+    /// Appends `text` as is.
+    pub fn write(&mut self, text:impl Into<String>) {
+        self.push_str(&text.into(), None);
+    }
+
+    /// Appends `text` followed by a newline.
+    pub fn writeln(&mut self, text:impl Into<String>) {
+        self.writeln_styled(text.into(), None);
+    }
+
+    /// Appends `text` in `color`.
+    pub fn write_colored(&mut self, text:impl Into<String>, color:Color) {
+        self.push_str(&text.into(), Some(color));
+    }
+
+    /// Appends `text` in `color` followed by a newline.
+    pub fn writeln_colored(&mut self, text:impl Into<String>, color:Color) {
+        self.writeln_styled(text.into(), Some(color));
+    }
+
+    /// Appends `message` in red as an error of `command`, e.g. `error: teleport: missing argument 1`.
+    /// Errors returned by commands added with [`ConsolePlugin::add_cmd_result`] are printed with it.
+    pub fn writeln_error(&mut self, command:&str, message:impl fmt::Display) {
+        self.writeln_styled(format!("error: {command}: {message}"), Some(Color::RED));
+    }
+
+    fn writeln_styled(&mut self, mut text:String, color:Option<Color>) {
+        text.push('\n');
+        self.push_str(&text, color);
+    }
+
+    /// Appends `rows` as a table, padding every column to its widest cell. Rows may have different lengths.
+    /// Columns only line up with a monospace [`ConsoleTheme::font`].
+    pub fn write_table(&mut self, rows:Vec<Vec<String>>) {
+        for line in format_table(&rows) {
+            self.writeln(line);
+        }
+    }
+
+    /// Empties the console output. The input line and [`CommandHistory`] are left alone.
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+}
+
+/// Lines of `rows` with the columns padded to their widest cell and two spaces between them.
+fn format_table(rows:&[Vec<String>]) -> Vec<String> {
+    let mut widths:Vec<usize> = Vec::new();
+    for row in rows {
+        for (column, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(column) {
+                Some(widest) => *widest = (*widest).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    rows.iter().map(|row| {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            let _ = write!(line, "{cell:<width$}  ");
+        }
+        line.truncate(line.trim_end().len());
+        line
+    }).collect()
+}
+
+/// The console as seen from a command system: prints like [`ConsoleWriter`], which it derefs to, and reads the
+/// command line, history and queue without touching the UI.
+/// ```
+/// # use console::*;
+/// fn last(mut console:Console) {
+///     let previous = console.history().iter().rev().nth(1).cloned();
+///     match previous {
+///         Some(line) => console.writeln(format!("Before this: {line}")),
+///         None => console.writeln("Nothing before this."),
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct Console<'w, 's> {
+    writer:ConsoleWriter<'w>,
+    history:Res<'w, CommandHistory>,
+    args:Option<Res<'w, CommandArgs>>,
+    pending:ResMut<'w, PendingCommands>,
+    marker:std::marker::PhantomData<&'s ()>,
+}
+
+impl<'w> std::ops::Deref for Console<'w, '_> {
+    type Target = ConsoleWriter<'w>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.writer
+    }
+}
+
+impl std::ops::DerefMut for Console<'_, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.writer
+    }
+}
+
+impl Console<'_, '_> {
+    /// The running command line, or `None` outside of a command.
+    pub fn args(&self) -> Option<&CommandArgs> {
+        self.args.as_deref()
+    }
+
+    /// Entered lines, oldest first. The running command's own line is the last one if it was typed.
+    pub fn history(&self) -> &[String] {
+        self.history.entries()
+    }
+
+    /// Queues `line` in [`PendingCommands`] to run after the current command.
+    pub fn run(&mut self, line:impl Into<String>) {
+        self.pending.push_back(line.into());
+    }
+
+    /// Everything printed so far, including lines scrolled out of view.
+    pub fn output(&self) -> &ConsoleOutput {
+        &self.writer.output
+    }
+}
+
+/// File that everything printed through [`ConsoleWriter`] is appended to, started with the `log_to_file` command.
+/// Every finished line is written out right away, so the log survives a crash.
+#[derive(Resource, Default)]
+pub struct ConsoleFileLog {
+    file:Option<(PathBuf, LineWriter<File>)>,
+}
+
+impl ConsoleFileLog {
+    /// Starts appending output to `path`, replacing the previous file if there was one.
+    pub fn start(&mut self, path:impl Into<PathBuf>) -> std::io::Result<()> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.stop();
+        self.file = Some((path, LineWriter::new(file)));
+        Ok(())
+    }
+
+    /// Stops logging and returns the path that was logged to.
+    pub fn stop(&mut self) -> Option<PathBuf> {
+        let (path, mut file) = self.file.take()?;
+        if let Err(err) = file.flush() {
+            warn!("Couldn't write console log {}: {err}", path.display());
+        }
+        Some(path)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    fn write(&mut self, text:&str) {
+        let Some((path, file)) = &mut self.file else { return };
+        if let Err(err) = file.write_all(text.as_bytes()) {
+            warn!("Stopped logging the console, couldn't write {}: {err}", path.display());
+            self.file = None;
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some((path, file)) = &mut self.file {
+            if let Err(err) = file.flush() {
+                warn!("Couldn't write console log {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+fn flush_file_log(mut file_log:ResMut<ConsoleFileLog>, mut exit:EventReader<AppExit>) {
+    if exit.read().last().is_some() {
+        file_log.flush();
+    }
+}
+
+/// Lines stay stamped with `0.0` without a [`TimePlugin`](bevy::time::TimePlugin), which headless consoles may run without.
+fn tick_output_clock(time:Option<Res<Time<Real>>>, mut output:ResMut<ConsoleOutput>) {
+    if let Some(time) = time {
+        output.bypass_change_detection().now = time.elapsed_seconds();
+    }
+}
+
+/// Rough line height, erring on the side of showing fewer lines so the newest ones never get clipped.
+fn line_height(theme:&ConsoleTheme) -> f32 {
+    theme.font_size * 1.25
+}
+
+/// Selects output lines by clicking and dragging over them. Clicking anywhere else clears the selection.
+fn select_output(
+    mouse:Res<ButtonInput<MouseButton>>,
+    windows:Query<&Window, With<PrimaryWindow>>,
+    output_field:Query<(&Node, &GlobalTransform), With<ConsoleOutputTag>>,
+    theme:Res<ConsoleTheme>,
+    mut output:ResMut<ConsoleOutput>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        return;
+    }
+    let (Ok(window), Ok((node, transform))) = (windows.get_single(), output_field.get_single()) else { return };
+    let Some(cursor) = window.cursor_position() else { return };
+    let rect = node.logical_rect(transform);
+    let line = output.line_at_row(((cursor.y - rect.min.y).max(0.) / line_height(&theme)) as usize);
+    if mouse.just_pressed(MouseButton::Left) {
+        output.selection = if rect.contains(cursor) { Some((line, line)) } else { None };
+    } else if let Some((from, to)) = output.selection {
+        if to != line {
+            output.selection = Some((from, line));
+        }
+    }
+}
+
+/// Scrolls the output with the mouse wheel while the cursor is over it. `lines` keeps the fraction of a line
+/// touchpads scroll by until it adds up to a whole one.
+fn scroll_output(
+    mut wheel:EventReader<MouseWheel>,
+    windows:Query<&Window, With<PrimaryWindow>>,
+    output_field:Query<(&Node, &GlobalTransform), With<ConsoleOutputTag>>,
+    config:Res<ConsoleConfig>,
+    theme:Res<ConsoleTheme>,
+    mut output:ResMut<ConsoleOutput>,
+    mut lines:Local<f32>,
+) {
+    let (Ok(window), Ok((node, transform))) = (windows.get_single(), output_field.get_single()) else { return };
+    if !window.cursor_position().is_some_and(|cursor| node.logical_rect(transform).contains(cursor)) {
+        wheel.clear();
+        return;
+    }
+    for event in wheel.read() {
+        *lines += match event.unit {
+            MouseScrollUnit::Line => event.y * config.wheel_scroll_lines as f32,
+            MouseScrollUnit::Pixel => event.y / line_height(&theme),
+        };
+    }
+    let whole = lines.trunc();
+    if whole != 0.0 {
+        *lines -= whole;
+        output.scroll_by(whole as isize);
+    }
+}
+
+/// Renders the part of [`ConsoleOutput`] the view is scrolled to.
+fn render_output(mut output:ResMut<ConsoleOutput>, theme:Res<ConsoleTheme>, config:Res<ConsoleConfig>, mut output_field:Query<(&mut Text, Ref<Node>), With<ConsoleOutputTag>>) {
+    let Ok((mut text, node)) = output_field.get_single_mut() else { return };
+    if !output.is_changed() && !node.is_changed() && !config.is_changed() {
+        return;
+    }
+    let line_height = line_height(&theme);
+    let visible = ((node.size().y / line_height) as usize).max(1);
+    let columns = (node.size().x / (theme.font_size * CHAR_WIDTH)) as usize;
+    if (output.visible, output.columns, output.timestamps) != (visible, columns, config.timestamps) {
+        (output.visible, output.columns, output.timestamps) = (visible, columns, config.timestamps);
+        output.clamp_scroll();
+    }
+    let range = output.visible_range();
+    let selected = output.selected_range();
+    text.sections = output.lines.range(range.clone()).zip(range).enumerate().map(|(idx, (line, line_idx))| {
+        let mut value = String::with_capacity(line.text.len() + 1);
+        if idx > 0 {
+            value.push('\n');
+        }
+        if config.timestamps {
+            value.push_str(&timestamp(line.time));
+        }
+        value.push_str(&line.text);
+        let color = if selected.as_ref().is_some_and(|selected| selected.contains(&line_idx)) {
+            theme.selection_color
+        } else {
+            line.color.unwrap_or(theme.output_color)
+        };
+        TextSection { value, style:theme.text_style(color) }
+    }).collect();
+}
+
+/// Prefix of a line shown with [`ConsoleConfig::timestamps`].
+fn timestamp(time:f32) -> String {
+    format!("[{time:.2}] ")
+}
+
+/// Log records waiting to be printed to the console. Bounded so nothing piles up if the console never drains it.
+static CAPTURED_LOGS:Mutex<VecDeque<(Level, String)>> = Mutex::new(VecDeque::new());
+const CAPTURED_LOGS_CAP:usize = 1024;
+
+/// Mirrors `info!`, `warn!` and friends into the console. Install it through [`bevy::log::LogPlugin`]:
+/// ```no_run
+/// # use bevy::{log::LogPlugin, prelude::*};
+/// App::new().add_plugins(DefaultPlugins.set(LogPlugin { update_subscriber: Some(console::capture_log), ..default() }));
+/// ```
+/// Records below [`ConsoleConfig::log_level`] are dropped, as is anything logged by the console itself.
+pub fn capture_log(subscriber:BoxedSubscriber) -> BoxedSubscriber {
+    Box::new(subscriber.with(ConsoleLogLayer))
+}
+
+struct ConsoleLogLayer;
+
+impl<S:Subscriber> Layer<S> for ConsoleLogLayer {
+    fn on_event(&self, event:&LogEvent<'_>, _ctx:Context<'_, S>) {
+        let metadata = event.metadata();
+        let target = metadata.target();
+        let crate_name = env!("CARGO_CRATE_NAME");
+        if target == crate_name || target.strip_prefix(crate_name).is_some_and(|rest| rest.starts_with("::")) {
+            return;
+        }
+        let mut message = LogMessage(String::new());
+        event.record(&mut message);
+        let Ok(mut logs) = CAPTURED_LOGS.lock() else { return };
+        if logs.len() >= CAPTURED_LOGS_CAP {
+            logs.pop_front();
+        }
+        logs.push_back((*metadata.level(), message.0));
+    }
+}
+
+/// Formats an event as its message followed by any other `key=value` fields.
+struct LogMessage(String);
+
+impl Visit for LogMessage {
+    fn record_debug(&mut self, field:&Field, value:&dyn fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, "{}={value:?}", field.name());
+        }
+    }
+}
+
+fn print_captured_logs(config:Res<ConsoleConfig>, mut writer:ConsoleWriter) {
+    let Ok(mut logs) = CAPTURED_LOGS.lock() else { return };
+    for (level, message) in logs.drain(..) {
+        if level <= config.log_level {
+            let color = match level {
+                Level::ERROR => Color::RED,
+                Level::WARN => Color::YELLOW,
+                Level::INFO => Color::WHITE,
+                _ => Color::GRAY,
+            };
+            writer.writeln_colored(format!("[{level}] {message}"), color);
+        }
+    }
+}
+
+/// Every command entered into the console this session, oldest first. Up/Down arrows walk through it.
+#[derive(Resource)]
+pub struct CommandHistory {
+    entries: Vec<String>,
+    /// Index of the recalled entry. Equal to `entries.len()` when nothing is recalled.
+    cursor: usize,
+    max_entries: usize,
+    /// The line being typed when browsing started, given back when stepping past the newest entry.
+    draft: String,
+}
+
+impl Default for CommandHistory {
+    fn default() -> Self {
+        Self::with_max_entries(1000)
+    }
+}
+
+impl CommandHistory {
+    /// An empty history remembering at most `max_entries` commands.
+    pub fn with_max_entries(max_entries:usize) -> Self {
+        Self { entries:Vec::new(), cursor:0, max_entries:max_entries.max(1), draft:String::new() }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    fn push(&mut self, cmd:String) {
+        if !cmd.is_empty() {
+            self.entries.push(cmd);
+            self.truncate();
+        }
+        self.reset_cursor();
+    }
+
+    fn truncate(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.max_entries);
+        self.entries.drain(..excess);
+    }
+
+    fn reset_cursor(&mut self) {
+        self.cursor = self.entries.len();
+        self.draft.clear();
+    }
+
+    /// Steps one entry back in time, stopping at the oldest one. `typed` is kept as the draft when browsing starts.
+    fn older(&mut self, typed:&str) -> Option<&str> {
+        let cursor = self.cursor.checked_sub(1)?;
+        if self.cursor == self.entries.len() {
+            typed.clone_into(&mut self.draft);
+        }
+        self.cursor = cursor;
+        Some(&self.entries[self.cursor])
+    }
+
+    /// Steps one entry forward in time, giving back the draft when stepping past the newest entry.
+    /// `None` if no entry is recalled, so the typed line stays as it is.
+    fn newer(&mut self) -> Option<&str> {
+        if self.cursor >= self.entries.len() {
+            return None;
+        }
+        self.cursor += 1;
+        Some(self.entries.get(self.cursor).unwrap_or(&self.draft))
+    }
+}
+
+fn persist_history_enabled(config:Res<ConsoleConfig>) -> bool {
+    config.persist_history
+}
+
+/// Fills [`CommandHistory`] from the history file, one command per line.
+fn load_history(config:Res<ConsoleConfig>, mut history:ResMut<CommandHistory>) {
+    match std::fs::read_to_string(&config.history_file) {
+        Ok(contents) => {
+            history.entries = contents.lines().filter(|line| !line.is_empty()).map(str::to_owned).collect();
+            history.truncate();
+            history.reset_cursor();
+        },
+        Err(err) => warn!("Starting with empty console history, couldn't read {}: {err}", config.history_file.display()),
+    }
+}
+
+fn save_history(config:Res<ConsoleConfig>, history:Res<CommandHistory>, mut exit:EventReader<AppExit>) {
+    if exit.read().last().is_none() {
+        return;
+    }
+    let path = &config.history_file;
+    let mut contents = history.entries.join("\n");
+    contents.push('\n');
+    let result = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(path, contents));
+    if let Err(err) = result {
+        warn!("Couldn't save console history to {}: {err}", path.display());
+    }
+}
+
+/// The line being typed. `cursor` is a byte index that always sits on a char boundary - every edit and cursor move
+/// steps over whole chars, so multibyte text like `é` or emoji is never split. Layout counts chars, see [`InputLine::cursor_chars`].
+#[derive(Resource, Default)]
+struct InputLine {
+    text:String,
+    cursor:usize,
+    /// Text being composed with an IME, shown at the cursor but not part of `text` until committed.
+    preedit:String,
+}
+
+impl InputLine {
+    /// Replaces the text, cut off after `max_len` chars, and puts the cursor at its end.
+    fn set(&mut self, mut text:String, max_len:Option<usize>) {
+        if let Some((idx, _)) = max_len.and_then(|max_len| text.char_indices().nth(max_len)) {
+            text.truncate(idx);
+        }
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    /// Empties the line, leaving just the prompt and the cursor on screen.
+    fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.preedit.clear();
+    }
+
+    /// Inserts `char` at the cursor unless the line already holds `max_len` chars. Returns whether it was inserted.
+    fn insert(&mut self, char:char, max_len:Option<usize>) -> bool {
+        if max_len.is_some_and(|max_len| self.text.chars().count() >= max_len) {
+            return false;
+        }
+        self.text.insert(self.cursor, char);
+        self.cursor += char.len_utf8();
+        true
+    }
+
+    /// Byte index of the char right before the cursor.
+    fn prev_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor].char_indices().next_back().map(|(idx, _)| idx)
+    }
+
+    /// Byte index right after the char under the cursor.
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.cursor..].chars().next().map(|char| self.cursor + char.len_utf8())
+    }
+
+    fn backspace(&mut self) {
+        if let Some(idx) = self.prev_boundary() {
+            self.text.remove(idx);
+            self.cursor = idx;
+        }
+    }
+
+    fn delete(&mut self) {
+        if let Some(end) = self.next_boundary() {
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    /// Byte index where the word before the cursor starts, skipping any whitespace right before the cursor.
+    fn prev_word_boundary(&self) -> usize {
+        self.text[..self.cursor].trim_end().char_indices().rev()
+            .find(|(_, char)| char.is_whitespace())
+            .map_or(0, |(idx, char)| idx + char.len_utf8())
+    }
+
+    /// Byte index where the word after the cursor ends, skipping any whitespace right after the cursor.
+    fn next_word_boundary(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let word = after.trim_start();
+        let word_start = self.cursor + after.len() - word.len();
+        word_start + word.find(char::is_whitespace).unwrap_or(word.len())
+    }
+
+    fn delete_word_back(&mut self) {
+        let start = self.prev_word_boundary();
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    fn delete_word_forward(&mut self) {
+        let end = self.next_word_boundary();
+        self.text.replace_range(self.cursor..end, "");
+    }
+
+    fn left(&mut self) {
+        self.cursor = self.prev_boundary().unwrap_or(0);
+    }
+
+    fn right(&mut self) {
+        self.cursor = self.next_boundary().unwrap_or(self.text.len());
+    }
+
+    fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Cursor position counted in chars, for layout.
+    fn cursor_chars(&self) -> usize {
+        self.text[..self.cursor].chars().count()
+    }
+
+    /// Byte index of the char at char position `n`, or the end of the line if there are fewer chars.
+    fn byte_index(&self, n:usize) -> usize {
+        self.text.char_indices().nth(n).map_or(self.text.len(), |(idx, _)| idx)
+    }
+}
+
+/// Rough width of a character relative to the font size, used to fit the input line into the console.
+const CHAR_WIDTH:f32 = 0.6;
+const CLIPPED:&str = "...";
+
+// Sections of the input line text. The command name and its arguments are split so they can be colored differently.
+const INPUT_PROMPT:usize = 0;
+const INPUT_NAME_BEFORE_CURSOR:usize = 1;
+const INPUT_ARGS_BEFORE_CURSOR:usize = 2;
+const INPUT_CURSOR:usize = 3;
+const INPUT_NAME_AFTER_CURSOR:usize = 4;
+const INPUT_ARGS_AFTER_CURSOR:usize = 5;
+
+/// Shows [`ConsoleConfig::prompt`] in front of the input line when it changes.
+fn apply_prompt(config:Res<ConsoleConfig>, mut input_field:Query<&mut Text, (With<ConsoleInputTag>, Without<ConsoleOutputTag>)>) {
+    let Ok(mut text) = input_field.get_single_mut() else { return };
+    if text.sections[INPUT_PROMPT].value != config.prompt {
+        text.sections[INPUT_PROMPT].value = config.prompt.to_string();
+    }
+}
+
+/// Shows the part of the input line around the cursor, scrolling horizontally when it doesn't fit.
+/// `start` is the first visible character. The command name is colored by whether it exists.
+fn render_input(
+    input:Res<InputLine>,
+    theme:Res<ConsoleTheme>,
+    map:Res<CommandMap>,
+    aliases:Res<CommandAliases>,
+    config:Res<ConsoleConfig>,
+    mut input_field:Query<(&mut Text, Ref<Node>), With<ConsoleInputTag>>,
+    mut start:Local<usize>,
+) {
+    let Ok((mut text, node)) = input_field.get_single_mut() else { return };
+    if !input.is_changed() && !node.is_changed() && !theme.is_changed() && !map.is_changed() && !aliases.is_changed() && !config.is_changed() {
+        return;
+    }
+    let sections = &mut text.sections;
+    let total = input.text.chars().count();
+    let cursor = input.cursor_chars();
+    let fits = (node.size().x / (theme.font_size * CHAR_WIDTH)) as usize;
+    // Leave room for the prompt, the cursor and the clipping markers on both sides
+    let cursor_width = sections[INPUT_CURSOR].value.chars().count();
+    let width = match fits.checked_sub(sections[INPUT_PROMPT].value.chars().count() + cursor_width + 2 * CLIPPED.len()) {
+        Some(width) if width > 0 => width,
+        _ if fits == 0 => total, // not laid out yet
+        _ => 1,
+    };
+    *start = (*start).min(cursor).max(cursor.saturating_sub(width)).min(total.saturating_sub(width));
+    let end = total.min(*start + width);
+
+    let name = split_name(&input.text).0;
+    let name_end = input.text.len() - input.text.trim_start().len() + name.len();
+    let name_color = if name.is_empty() {
+        theme.input_color
+    } else if find_name(&map, name, config.case_insensitive).is_some() || find_name(&aliases, name, config.case_insensitive).is_some() {
+        theme.known_command_color
+    } else {
+        theme.unknown_command_color
+    };
+    let split = |from:usize, to:usize| {
+        let mid = name_end.clamp(from, to);
+        (&input.text[from..mid], &input.text[mid..to])
+    };
+    let (name_before, args_before) = split(input.byte_index(*start), input.cursor);
+    let (name_after, args_after) = split(input.cursor, input.byte_index(end));
+    sections[INPUT_NAME_BEFORE_CURSOR].value = format!("{}{name_before}", if *start > 0 { CLIPPED } else { "" });
+    sections[INPUT_ARGS_BEFORE_CURSOR].value = format!("{args_before}{}", input.preedit);
+    name_after.clone_into(&mut sections[INPUT_NAME_AFTER_CURSOR].value);
+    sections[INPUT_ARGS_AFTER_CURSOR].value = format!("{args_after}{}", if end < total { CLIPPED } else { "" });
+    for (section, color) in [(INPUT_NAME_BEFORE_CURSOR, name_color), (INPUT_NAME_AFTER_CURSOR, name_color),
+            (INPUT_ARGS_BEFORE_CURSOR, theme.input_color), (INPUT_ARGS_AFTER_CURSOR, theme.input_color)] {
+        sections[section].style.color = color;
+    }
+}
+
+/// What is typed in the console input line, kept up to date every frame.
+#[derive(Resource, Default, Debug, Clone, Deref)]
+pub struct CurrentInput(String);
+
+/// Sent whenever the text in the console input line changes.
+#[derive(Event, Debug, Clone)]
+pub struct InputChanged {
+    pub text:String,
+}
+
+fn publish_input(input:Res<InputLine>, mut current:ResMut<CurrentInput>, mut changed:EventWriter<InputChanged>) {
+    if current.0 != input.text {
+        current.0.clone_from(&input.text);
+        changed.send(InputChanged { text:input.text.clone() });
+    }
+}
+
+/// Command names matching the typed name, shown below the input line.
+#[derive(Resource, Default)]
+struct Suggestions {
+    names:Vec<String>,
+    /// Matches left out of `names` by [`ConsoleConfig::max_suggestions`].
+    more:usize,
+    /// Highlighted entry, picked with Tab or the arrow keys and filled in with Enter.
+    selected:Option<usize>,
+}
+
+impl Suggestions {
+    fn selected(&self) -> Option<&str> {
+        self.selected.and_then(|i| self.names.get(i)).map(String::as_str)
+    }
+
+    fn select_next(&mut self) {
+        if !self.names.is_empty() {
+            self.selected = Some(self.selected.map_or(0, |i| (i + 1) % self.names.len()));
+        }
+    }
+
+    fn select_prev(&mut self) {
+        if !self.names.is_empty() {
+            self.selected = Some(match self.selected {
+                Some(i) if i > 0 => i - 1,
+                _ => self.names.len() - 1,
+            });
+        }
+    }
+}
+
+/// Recomputes the suggestions while the command name is being typed.
+fn update_suggestions(input:Res<InputLine>, map:Res<CommandMap>, aliases:Res<CommandAliases>, config:Res<ConsoleConfig>, mut suggestions:ResMut<Suggestions>) {
+    let typed = input.text.trim_start();
+    let mut names:Vec<String> = Vec::new();
+    if !typed.is_empty() && !typed.contains(char::is_whitespace) {
+        names = map.keys().chain(aliases.keys())
+            .filter(|cmd| starts_with(cmd, typed, config.case_insensitive))
+            .map(|cmd| cmd.to_string())
+            .collect();
+        names.sort_unstable();
+    }
+    let more = names.len().saturating_sub(config.max_suggestions);
+    names.truncate(config.max_suggestions);
+    if names != suggestions.names || more != suggestions.more || suggestions.selected.is_some() {
+        *suggestions = Suggestions { names, more, selected:None };
+    }
+}
+
+fn render_suggestions(suggestions:Res<Suggestions>, theme:Res<ConsoleTheme>, mut panel:Query<(&mut Text, &mut Style), With<ConsoleSuggestionsTag>>) {
+    let Ok((mut text, mut style)) = panel.get_single_mut() else { return };
+    style.display = if suggestions.names.is_empty() { Display::None } else { Display::Flex };
+    text.sections = suggestions.names.iter().enumerate().map(|(i, name)| {
+        let color = if suggestions.selected == Some(i) { Color::YELLOW } else { theme.output_color };
+        let end = if i + 1 < suggestions.names.len() { "\n" } else { "" };
+        TextSection::new(format!("{name}{end}"), theme.text_style(color))
+    }).collect();
+    if suggestions.more > 0 {
+        text.sections.push(TextSection::new(format!("\n+{} more", suggestions.more), theme.text_style(Color::GRAY)));
+    }
+}
+
+/// Lets the OS input method compose text into the console while it is open.
+fn enable_ime(config:Res<ConsoleConfig>, mut windows:Query<&mut Window, With<PrimaryWindow>>) {
+    if let Ok(mut window) = windows.get_single_mut() {
+        window.ime_enabled = true;
+        // Candidate box near the input line, which sits at the bottom of the console
+        let size = config.height_percent / 100.;
+        window.ime_position = match config.anchor {
+            ConsoleAnchor::Top => Vec2::new(12., window.height() * size),
+            ConsoleAnchor::Bottom | ConsoleAnchor::Left => Vec2::new(12., window.height()),
+            ConsoleAnchor::Right => Vec2::new(window.width() * (1. - size) + 12., window.height()),
+        };
+    }
+}
+
+fn disable_ime(mut windows:Query<&mut Window, With<PrimaryWindow>>, mut input:ResMut<InputLine>) {
+    if let Ok(mut window) = windows.get_single_mut() {
+        window.ime_enabled = false;
+    }
+    if !input.preedit.is_empty() {
+        input.preedit.clear();
+    }
+}
+
+/// Shows IME compositions inline and inserts them once committed.
+fn ime_input(
+    mut events:EventReader<Ime>,
+    config:Res<ConsoleConfig>,
+    mut input:ResMut<InputLine>,
+    mut history:ResMut<CommandHistory>,
+    mut entered:EventWriter<ConsoleCommandEntered>,
+    mut submitted:EventWriter<CommandSubmitted>,
+) {
+    for event in events.read() {
+        match event {
+            Ime::Preedit { value, .. } if *value != input.preedit => value.clone_into(&mut input.preedit),
+            Ime::Commit { value, .. } => {
+                input.preedit.clear();
+                insert_lines(value, &mut input, config.max_input_len, &mut entered, &mut submitted);
+                history.reset_cursor();
+            },
+            Ime::Disabled { .. } if !input.preedit.is_empty() => input.preedit.clear(),
+            _ => (),
+        }
+    }
+}
+
+/// Sends the input line off as a command, like pressing Enter does.
+fn submit_input(input:&mut InputLine, entered:&mut EventWriter<ConsoleCommandEntered>, submitted:&mut EventWriter<CommandSubmitted>) {
+    // The events carry their own copy of the line, so it can be cleared right away. `submit_command` adds that copy
+    // to history and the queue later this frame.
+    entered.send(ConsoleCommandEntered { line:input.text.clone() });
+    submitted.send(CommandSubmitted { line:input.text.clone() });
+    input.clear();
+}
+
+/// Inserts `text` at the cursor, submitting the input at every newline so a multi-line paste runs line by line
+/// like in a terminal. Blank lines are skipped and the last, unterminated line is left in the input.
+fn insert_lines(
+    text:&str,
+    input:&mut InputLine,
+    max_len:Option<usize>,
+    entered:&mut EventWriter<ConsoleCommandEntered>,
+    submitted:&mut EventWriter<CommandSubmitted>,
+) {
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 && !input.text.trim().is_empty() {
+            submit_input(input, entered, submitted);
+        }
+        for char in line.chars().filter(|char| !char.is_control()) {
+            input.insert(char, max_len);
+        }
+    }
+}
+
+fn ctrl_pressed(kbd:&ButtonInput<KeyCode>) -> bool {
+    kbd.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+}
+
+fn shift_pressed(kbd:&ButtonInput<KeyCode>) -> bool {
+    kbd.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+}
+
+/// Kept alive for the whole run - on some platforms the copied text disappears together with the clipboard handle.
+#[cfg(feature = "clipboard")]
+static CLIPBOARD:Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+/// Runs `f` with the system clipboard, or returns `None` if there is no clipboard backend.
+#[cfg(feature = "clipboard")]
+fn with_clipboard<T>(f:impl FnOnce(&mut arboard::Clipboard) -> Option<T>) -> Option<T> {
+    let mut clipboard = CLIPBOARD.lock().ok()?;
+    if clipboard.is_none() {
+        *clipboard = arboard::Clipboard::new().ok();
+    }
+    f(clipboard.as_mut()?)
+}
+
+#[cfg(feature = "clipboard")]
+fn clipboard_get() -> Option<String> {
+    with_clipboard(|clipboard| clipboard.get_text().ok())
+}
+
+/// Returns false if the clipboard couldn't be written.
+#[cfg(feature = "clipboard")]
+fn clipboard_set(text:String) -> bool {
+    with_clipboard(|clipboard| clipboard.set_text(text).ok()).is_some()
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn clipboard_get() -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn clipboard_set(_text:String) -> bool {
+    false
+}
+
+/// Blinks the cursor section, keeping it shown for a full interval after every edit or cursor move.
+fn blink_cursor(
+    time:Res<Time<Real>>,
+    config:Res<ConsoleConfig>,
+    theme:Res<ConsoleTheme>,
+    input:Res<InputLine>,
+    mut elapsed:Local<f32>,
+    mut input_field:Query<&mut Text, (With<ConsoleInputTag>, Without<ConsoleOutputTag>)>,
+) {
+    if theme.cursor_char.is_none() {
+        return;
+    }
+    let interval = config.cursor_blink_interval;
+    if input.is_changed() || interval <= 0.0 {
+        *elapsed = 0.0;
+    } else {
+        *elapsed = (*elapsed + time.delta_seconds()) % (2.0 * interval);
+    }
+    let visible = *elapsed < interval || interval <= 0.0;
+    let alpha = if visible { 1.0 } else { 0.0 };
+    let Ok(mut text) = input_field.get_single_mut() else { return };
+    // Only touch the text when the alpha flips so it isn't laid out again every frame.
+    if text.sections[INPUT_CURSOR].style.color.a() != alpha {
+        text.sections[INPUT_CURSOR].style.color.set_a(alpha);
+    }
+}
+
+/// Editing keys that repeat while held.
+const REPEAT_KEYS:[KeyCode; 6] = [KeyCode::Backspace, KeyCode::Delete, KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::ArrowUp, KeyCode::ArrowDown];
+
+/// Repeats the last pressed of [`REPEAT_KEYS`] while it is held. Typed characters are repeated by the OS already.
+#[derive(Default)]
+struct KeyRepeat {
+    key:Option<KeyCode>,
+    /// Seconds until the next repeat.
+    timer:f32,
+}
+
+impl KeyRepeat {
+    /// Returns the key to act on again this frame, if any.
+    fn update(&mut self, kbd:&ButtonInput<KeyCode>, delta:f32, config:&ConsoleConfig) -> Option<KeyCode> {
+        if let Some(key) = REPEAT_KEYS.into_iter().find(|key| kbd.just_pressed(*key)) {
+            self.key = Some(key);
+            self.timer = config.key_repeat_delay;
+            return None;
+        }
+        let key = self.key.filter(|key| kbd.pressed(*key));
+        self.key = key;
+        self.timer -= delta;
+        if key.is_some() && self.timer <= 0.0 && config.key_repeat_interval > 0.0 {
+            self.timer = config.key_repeat_interval;
+            return key;
+        }
+        None
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn text_input(
+    mut evr_char: ResMut<Events<ReceivedCharacter>>,
+    kbd: Res<ButtonInput<KeyCode>>,
+    map:Res<CommandMap>,
+    aliases:Res<CommandAliases>,
+    config:Res<ConsoleConfig>,
+    mut history:ResMut<CommandHistory>,
+    mut input:ResMut<InputLine>,
+    mut writer:ConsoleWriter,
+    mut submitted:EventWriter<CommandSubmitted>,
+    mut entered:EventWriter<ConsoleCommandEntered>,
+    mut typed:EventWriter<ConsoleKeyTyped>,
+    mut next:ResMut<NextState<ConsoleState>>,
+    mut suggestions:ResMut<Suggestions>,
+    mut arg_candidates:ResMut<ArgCandidates>,
+    time:Res<Time<Real>>,
+    mut repeat:Local<KeyRepeat>,
+) {
+    if !input.preedit.is_empty() {
+        // Keys belong to the IME until the composition is committed
+        return;
+    }
+    let repeated = repeat.update(&kbd, time.delta_seconds(), &config);
+    let pressed = |key:KeyCode| kbd.just_pressed(key) || repeated == Some(key);
+    if let (true, Some(name)) = (kbd.just_pressed(KeyCode::Enter), suggestions.selected()) {
+        input.set(format!("{name} "), config.max_input_len);
+    } else if pressed(KeyCode::ArrowUp) && suggestions.selected.is_some() {
+        suggestions.select_prev();
+    } else if pressed(KeyCode::ArrowDown) && suggestions.selected.is_some() {
+        suggestions.select_next();
+    } else if kbd.just_pressed(KeyCode::Enter) {
+        submit_input(&mut input, &mut entered, &mut submitted);
+        // The queued command runs whatever state the console is in.
+        if config.close_on_submit && !shift_pressed(&kbd) {
+            next.set(ConsoleState::AnimatingClosed);
+        }
+    } else if pressed(KeyCode::ArrowUp) {
+        if let Some(cmd) = history.older(&input.text) {
+            input.set(cmd.to_owned(), config.max_input_len);
+        }
+    } else if pressed(KeyCode::ArrowDown) {
+        if let Some(cmd) = history.newer() {
+            input.set(cmd.to_owned(), config.max_input_len);
+        }
+    } else if pressed(KeyCode::ArrowLeft) {
+        input.left();
+    } else if pressed(KeyCode::ArrowRight) {
+        input.right();
+    } else if kbd.just_pressed(KeyCode::Home) {
+        input.home();
+    } else if kbd.just_pressed(KeyCode::End) {
+        input.end();
+    } else if kbd.just_pressed(KeyCode::PageUp) {
+        writer.output.scroll_up();
+    } else if kbd.just_pressed(KeyCode::PageDown) {
+        writer.output.scroll_down();
+    } else if pressed(KeyCode::Backspace) {
+        if ctrl_pressed(&kbd) {
+            input.delete_word_back();
+        } else {
+            input.backspace();
+        }
+    } else if pressed(KeyCode::Delete) {
+        if ctrl_pressed(&kbd) {
+            input.delete_word_forward();
+        } else {
+            input.delete();
+        }
+    } else if ctrl_pressed(&kbd) && kbd.just_pressed(KeyCode::KeyV) {
+        if let Some(text) = clipboard_get() {
+            insert_lines(&text, &mut input, config.max_input_len, &mut entered, &mut submitted);
+            history.reset_cursor();
+        }
+    } else if ctrl_pressed(&kbd) && shift_pressed(&kbd) && kbd.just_pressed(KeyCode::KeyC) {
+        if let Some(text) = writer.output.selected_text() {
+            clipboard_set(text);
+        }
+    } else if ctrl_pressed(&kbd) && kbd.just_pressed(KeyCode::KeyC) {
+        clipboard_set(input.text.clone());
+    } else if kbd.just_pressed(KeyCode::Tab) {
+        let (name, _) = split_name(&input.text);
+        if input.text.trim_start().len() > name.len() {
+            // The name is complete, the command's completer has already been asked for its arguments
+            let Some(candidates) = arg_candidates.0.take() else { return };
+            let partial = input.text.rsplit(char::is_whitespace).next().unwrap_or_default().to_owned();
+            let matches:Vec<&str> = candidates.iter().map(String::as_str)
+                .filter(|arg| starts_with(arg, &partial, config.case_insensitive))
+                .collect();
+            let head = input.text[..input.text.len() - partial.len()].to_owned();
+            if !complete(&mut input, &mut writer, &head, &partial, &matches, &config) {
+                writer.writeln_colored("No completions.", Color::YELLOW);
+            }
+        } else {
+            let matches:Vec<&str> = map.keys().chain(aliases.keys()).map(|cmd| cmd.as_ref())
+                .filter(|cmd| starts_with(cmd, &input.text, config.case_insensitive))
+                .collect();
+            let typed = input.text.clone();
+            let extends = matches.len() == 1 || common_prefix(&matches).chars().count() > typed.chars().count();
+            if !extends && !suggestions.names.is_empty() && suggestions.more == 0 {
+                // Nothing left to complete, pick from the suggestion panel instead of listing the matches.
+                // Matches that didn't fit in the panel are listed instead, so all of them can be seen.
+                suggestions.select_next();
+            } else if !complete(&mut input, &mut writer, "", &typed, &matches, &config) {
+                let names = map.keys().chain(aliases.keys()).map(|cmd| cmd.as_ref());
+                match fuzzy_matches(names, typed.trim(), config.case_insensitive).as_slice() {
+                    [] => writer.writeln_colored("No commands start with that.", Color::YELLOW),
+                    close => writer.writeln_colored(format!("No commands start with that. Similar: {}", close.iter().take(FUZZY_LIMIT).copied().collect::<Vec<_>>().join(" ")), Color::YELLOW),
+                }
+            }
+        }
+    } else {
+        // The toggle key has been consumed by `consume_toggle_key` already
+        for ev in evr_char.drain() {
+            // ignore control (special) characters, except newlines from text pasted as typed characters.
+            // Enter arrives as '\r' and is handled above.
+            for char in ev.char.chars() {
+                if char == '\n' {
+                    insert_lines("\n", &mut input, config.max_input_len, &mut entered, &mut submitted);
+                } else if !char.is_control() && input.insert(char, config.max_input_len) {
+                    typed.send(ConsoleKeyTyped { char });
+                    history.reset_cursor();
+                }
+            }
+        }
+    }
+}
+
+/// Argument completions for the input line, gathered with world access right before `text_input` handles Tab.
+#[derive(Resource, Default)]
+struct ArgCandidates(Option<Vec<String>>);
+
+fn tab_pressed(kbd:Res<ButtonInput<KeyCode>>) -> bool {
+    kbd.just_pressed(KeyCode::Tab)
+}
+
+/// Asks the completer of the command being typed for candidates of the argument under the cursor.
+fn gather_arg_candidates(world:&mut World) {
+    let input = world.resource::<InputLine>();
+    let (name, rest) = split_name(&input.text);
+    let completer = (input.text.trim_start().len() > name.len())
+        .then(|| find_name(world.resource::<CommandMap>(), name, world.resource::<ConsoleConfig>().case_insensitive))
+        .flatten()
+        .and_then(|(_, info)| info.completer.clone());
+    let candidates = completer.map(|completer| {
+        let mut args = tokenize(rest);
+        if input.text.ends_with(char::is_whitespace) {
+            args.push(String::new());
+        }
+        let args:Vec<&str> = args.iter().map(String::as_str).collect();
+        completer.complete(&args, world)
+    });
+    world.resource_mut::<ArgCandidates>().0 = candidates;
+}
+
+/// Replaces `partial` at the end of the input with the single match or the longest common prefix of `matches`,
+/// or lists them sorted, at most [`ConsoleConfig::max_completions`] of them, if that wouldn't add anything.
+/// Returns false if there is nothing to complete with.
+fn complete(input:&mut InputLine, writer:&mut ConsoleWriter, head:&str, partial:&str, matches:&[&str], config:&ConsoleConfig) -> bool {
+    let limit = config.max_completions;
+    match matches {
+        [] => return false,
+        [single] => input.set(format!("{head}{single} "), config.max_input_len),
+        _ => {
+            let prefix = common_prefix(matches);
+            if prefix.chars().count() > partial.chars().count() {
+                input.set(format!("{head}{prefix}"), config.max_input_len);
+            } else {
+                let mut sorted = matches.to_vec();
+                sorted.sort_unstable();
+                sorted.dedup();
+                let shown = if limit == 0 { sorted.len() } else { limit.min(sorted.len()) };
+                let mut listing = sorted[..shown].join(" ");
+                if shown < sorted.len() {
+                    listing.push_str(&format!(" ...and {} more", sorted.len() - shown));
+                }
+                writer.writeln(listing);
+            }
+        }
+    }
+    true
+}
+
+/// Sent for every character typed into the input line, e.g. to play a key click. Pasted text and characters
+/// refused by [`ConsoleConfig::max_input_len`] don't send it.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ConsoleKeyTyped {
+    pub char:char,
+}
+
+/// Sent when Enter submits the input line, right before its [`CommandSubmitted`]. Unlike that one it is never sent
+/// for lines submitted from code.
+#[derive(Event, Debug, Clone)]
+pub struct ConsoleCommandEntered {
+    pub line:String,
+}
+
+/// Sent when a line is entered in the console. [`submit_command`] then adds it to [`CommandHistory`], echoes it
+/// and queues it in [`PendingCommands`]. It runs later in the same frame, after [`ConsoleSet`], unless lines queued
+/// before it use up [`ConsoleConfig::max_commands_per_frame`]. Send one yourself to submit a line as if it was typed.
+#[derive(Event, Debug, Clone)]
+pub struct CommandSubmitted {
+    pub line:String,
+}
+
+/// Handles [`CommandSubmitted`]. Order your systems `.before(submit_command)` to see a line before it is echoed.
+pub fn submit_command(
+    mut submitted:EventReader<CommandSubmitted>,
+    config:Res<ConsoleConfig>,
+    mut history:ResMut<CommandHistory>,
+    mut writer:ConsoleWriter,
+    mut pending:ResMut<PendingCommands>,
+) {
+    for CommandSubmitted { line } in submitted.read() {
+        // History first, so a command that reads it sees its own line.
+        history.push(line.clone());
+        if config.echo_input {
+            writer.writeln(line.as_str());
+        }
+        pending.push_back(line.clone());
+    }
+}
+
+/// Sent right before a command's system is run.
+#[derive(Event, Debug, Clone)]
+pub struct CommandExecuted {
+    pub name:String,
+    /// Everything after the command name.
+    pub args:String,
+}
+
+/// Sent when the entered command isn't registered.
+#[derive(Event, Debug, Clone)]
+pub struct CommandNotFound {
+    pub name:String,
+}
+
+/// How the last command the console ran ended, like `$?` in a shell. Printed by the `last_result` command.
+/// Only commands added with [`ConsolePlugin::add_cmd_result`] can report an [`LastCommandResult::Error`] themselves,
+/// every other command that runs counts as [`LastCommandResult::Ok`].
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub enum LastCommandResult {
+    /// The command ran without returning an error. Also the value before any command ran.
+    #[default]
+    Ok,
+    /// No command or alias with this name is registered.
+    NotFound(String),
+    /// The command returned this error, or was refused before running - by its usage, the command filter or an alias loop.
+    Error(String),
+}
+
+impl LastCommandResult {
+    pub fn is_ok(&self) -> bool {
+        *self == LastCommandResult::Ok
+    }
+}
+
+impl fmt::Display for LastCommandResult {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LastCommandResult::Ok => f.write_str("ok"),
+            LastCommandResult::NotFound(name) => write!(f, "not found: {name}"),
+            LastCommandResult::Error(message) => write!(f, "error: {message}"),
+        }
+    }
+}
+
+/// Error returned by the running command, set by [`report_cmd_error`] and moved into [`LastCommandResult`] once it has finished.
+#[derive(Resource, Default)]
+struct CommandError(Option<String>);
+
+/// Sets [`LastCommandResult`] after a command's system has run.
+fn finish_cmd(world:&mut World) {
+    let error = world.resource_mut::<CommandError>().0.take();
+    *world.resource_mut::<LastCommandResult>() = error.map_or(LastCommandResult::Ok, LastCommandResult::Error);
+}
+
+/// Command lines waiting to run, oldest first. Up to [`ConsoleConfig::max_commands_per_frame`] are taken off the front
+/// and run every frame, so lines pushed by a running command run after those.
+/// Everything the console executes goes through here, so pushing a line is how you run a command from code:
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// fn reset_level(mut pending:ResMut<PendingCommands>) {
+///     pending.push_back("despawn_all".into());
+///     pending.push_back("spawn player".into());
+/// }
+/// ```
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct PendingCommands(VecDeque<String>);
+
+/// [`run_cmd`] registered as a one-shot system, so [`run_pending`] can run it after setting [`CommandArgs`].
+#[derive(Resource)]
+struct RunCmd(SystemId);
+
+fn run_pending(world:&mut World) {
+    run_queued(world);
+    // Lines queued for the other consoles run as well, each console standing in for the active one meanwhile.
+    let waiting:Vec<_> = world.resource::<ConsoleInstances>().parked.iter()
+        .filter(|(_, parked)| !parked.pending.is_empty())
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in waiting {
+        if let Some(parked) = unpark(world, &id) {
+            run_queued(world);
+            repark(world, parked);
+        }
+    }
+}
+
+/// Runs the lines in [`PendingCommands`], up to [`ConsoleConfig::max_commands_per_frame`] of them.
+fn run_queued(world:&mut World) {
+    let mut budget = world.resource::<ConsoleConfig>().max_commands_per_frame.max(1);
+    while budget > 0 {
+        let Some(line) = world.resource_mut::<PendingCommands>().pop_front() else { break };
+        if run_first_cmd(world, &line) {
+            budget -= 1;
+        }
+    }
+}
+
+/// Runs the first command of `line` and puts the others back at the front of [`PendingCommands`], so `a; b; c` runs `a`
+/// now, followed by `b` and `c`. Returns false if `line` has no command.
+fn run_first_cmd(world:&mut World, line:&str) -> bool {
+    let segments = split_commands(line);
+    let Some((first, rest)) = segments.split_first() else { return false };
+    let mut pending = world.resource_mut::<PendingCommands>();
+    for segment in rest.iter().rev() {
+        pending.push_front(segment.to_string());
+    }
+    world.insert_resource(CommandArgs::new(first.to_string()));
+    // Applies the deferred `run_system` as well, so the command has finished - and queued anything it runs next,
+    // like the rest of an alias - before the following line is taken.
+    let run = world.resource::<RunCmd>().0;
+    let _ = world.run_system(run);
+    true
+}
+
+/// Splits `line` on `;` that aren't quoted or escaped, following the same rules as [`tokenize`].
+/// Segments are trimmed and empty ones dropped.
+fn split_commands(line:&str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = line.char_indices();
+    while let Some((idx, char)) = chars.next() {
+        match (char, quote) {
+            ('\\', _) => {
+                chars.next();
+            },
+            (char, Some(open)) if char == open => quote = None,
+            ('"' | '\'', None) => quote = Some(char),
+            (';', None) => {
+                segments.push(&line[start..idx]);
+                start = idx + 1;
+            },
+            _ => (),
+        }
+    }
+    segments.push(&line[start..]);
+    segments.into_iter().map(str::trim).filter(|segment| !segment.is_empty()).collect()
+}
+
+/// Longest string every entry of `names` starts with.
+fn common_prefix<'a>(names:&[&'a str]) -> &'a str {
+    let Some((first, rest)) = names.split_first() else { return "" };
+    rest.iter().fold(*first, |prefix, name| {
+        let len = prefix.char_indices().zip(name.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((idx, char), _)| idx + char.len_utf8());
+        &prefix[..len]
+    })
+}
+
+/// Looks up `name` in a command or alias map, falling back to an ASCII case-insensitive search if requested.
+fn find_name<'a, V>(map:&'a HashMap<Cow<'static, str>, V>, name:&str, case_insensitive:bool) -> Option<(&'a Cow<'static, str>, &'a V)> {
+    map.get_key_value(name).or_else(|| {
+        if case_insensitive {
+            map.iter().find(|(key, _)| key.eq_ignore_ascii_case(name))
+        } else {
+            None
+        }
+    })
+}
+
+fn starts_with(name:&str, prefix:&str, case_insensitive:bool) -> bool {
+    if case_insensitive {
+        name.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    } else {
+        name.starts_with(prefix)
+    }
+}
+
+/// Most fuzzy candidates listed by Tab.
+const FUZZY_LIMIT:usize = 5;
+
+/// Scores `name` containing the chars of `pattern` in order, e.g. `tlport` in `teleport`. Runs of consecutive chars and
+/// a matching first char score higher, extra chars in `name` lower. `None` if `pattern` isn't a subsequence of `name`.
+fn fuzzy_score(name:&str, pattern:&str, case_insensitive:bool) -> Option<i32> {
+    let eq = |a:char, b:char| if case_insensitive { a.eq_ignore_ascii_case(&b) } else { a == b };
+    let mut chars = name.chars().enumerate();
+    let mut score = 0;
+    let mut last = None;
+    for wanted in pattern.chars() {
+        let (idx, _) = chars.find(|(_, char)| eq(*char, wanted))?;
+        score += match (idx, last) {
+            (0, _) => 3,
+            (idx, Some(last)) if idx == last + 1 => 2,
+            _ => 1,
+        };
+        last = Some(idx);
+    }
+    Some(score - (name.chars().count() - pattern.chars().count()) as i32)
+}
+
+/// Names `pattern` fuzzily matches, best first.
+fn fuzzy_matches<'a>(names:impl Iterator<Item = &'a str>, pattern:&str, case_insensitive:bool) -> Vec<&'a str> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+    let mut scored:Vec<_> = names.filter_map(|name| Some((fuzzy_score(name, pattern, case_insensitive)?, name))).collect();
+    scored.sort_unstable_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.cmp(b)));
+    scored.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Number of single char insertions, deletions, substitutions and swaps of neighbouring chars turning `a` into `b`
+/// (the optimal string alignment distance), so the common `ehco` for `echo` is a single edit.
+fn edit_distance(a:&str, b:&str) -> usize {
+    let (a, b):(Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // Distances from the prefixes of `a` one and two chars shorter than the current one.
+    let mut before:Vec<usize> = Vec::new();
+    let mut previous:Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// The name fewest edits away from `typo`, if it's close enough to be a likely typo.
+fn closest_by_edits<'a>(names:impl Iterator<Item = &'a str>, typo:&str, case_insensitive:bool) -> Option<&'a str> {
+    let fold = |name:&str| if case_insensitive { name.to_ascii_lowercase() } else { name.to_owned() };
+    let typo_folded = fold(typo);
+    let threshold = (typo.chars().count() / 3).clamp(1, 3);
+    names.map(|name| (edit_distance(&fold(name), &typo_folded), name))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min()
+        .map(|(_, name)| name)
+}
+
+/// The least and most arguments a usage string like `teleport <x> <y> [z]` allows. `None` means no limit.
+fn usage_arity(usage:&str) -> (usize, Option<usize>) {
+    let (mut min, mut max) = (0, Some(0));
+    for arg in usage.split_whitespace().skip(1) {
+        if !arg.starts_with('[') {
+            min += 1;
+        }
+        max = if arg.contains("...") { None } else { max.map(|max| max + 1) };
+    }
+    (min, max)
+}
+
+/// What to do with a command, returned by the callback given to [`ConsolePlugin::set_command_filter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Run the command as it is.
+    Allow,
+    /// Don't run the command and print the reason instead.
+    Deny(String),
+    /// Run this line instead. Aliases in it are expanded, but it is not filtered again.
+    Rewrite(String),
+}
+
+type FilterFn = dyn Fn(&str, &[&str]) -> Filter + Send + Sync;
+
+#[derive(Resource, Default)]
+struct CommandFilter(Option<Arc<FilterFn>>);
+
+/// Expands `cmd` if it starts with an alias. An alias of several commands runs the first now and puts the others at
+/// the front of `pending` to run right after it. Fails if the aliases loop.
+fn expand_alias(cmd:&mut CommandArgs, aliases:&CommandAliases, case_insensitive:bool, pending:&mut PendingCommands) -> Result<(), String> {
+    match aliases.expand(cmd, case_insensitive) {
+        Ok(Some(line)) => {
+            let segments = split_commands(&line);
+            for segment in segments.iter().skip(1).rev() {
+                pending.push_front(segment.to_string());
+            }
+            cmd.replace(segments.first().map_or_else(String::new, |first| first.to_string()));
+            Ok(())
+        },
+        Ok(None) => Ok(()),
+        Err(chain) => Err(format!("Alias loop detected: {}", chain.join(" -> "))),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_cmd(
+    mut cmd:ResMut<CommandArgs>,
+    map: Res<CommandMap>,
+    aliases:Res<CommandAliases>,
+    filter:Res<CommandFilter>,
+    config:Res<ConsoleConfig>,
+    cvars:Res<Cvars>,
+    mut writer:ConsoleWriter,
+    mut executed:EventWriter<CommandExecuted>,
+    mut not_found:EventWriter<CommandNotFound>,
+    mut result:ResMut<LastCommandResult>,
+    mut pending:ResMut<PendingCommands>,
+    mut commands:Commands,
+) {
+    if let Err(message) = expand_alias(&mut cmd, &aliases, config.case_insensitive, &mut pending) {
+        writer.writeln_colored(message.as_str(), Color::RED);
+        *result = LastCommandResult::Error(message);
+        return;
+    }
+    if let (Some(filter), Some(call)) = (&filter.0, cmd.tokens.first()) {
+        let name = find_name(&map, call, config.case_insensitive).map_or(call.as_str(), |(name, _)| name.as_ref());
+        match filter(name, &cmd.args()) {
+            Filter::Allow => (),
+            Filter::Deny(reason) => {
+                writer.writeln_colored(reason.as_str(), Color::RED);
+                *result = LastCommandResult::Error(reason);
+                return;
+            },
+            Filter::Rewrite(line) => {
+                cmd.replace(line);
+                if let Err(message) = expand_alias(&mut cmd, &aliases, config.case_insensitive, &mut pending) {
+                    writer.writeln_colored(message.as_str(), Color::RED);
+                    *result = LastCommandResult::Error(message);
+                    return;
+                }
+            },
+        }
+    }
+    if let Some(call) = cmd.tokens.first() {
+        if let Some((name, info)) = find_name(&map, call, config.case_insensitive) {
+            if info.permission > PermissionLevel::allowed(&cvars) {
+                let message = format!("{name} is a {} command, set {CHEATS_CVAR} {} to use it", info.permission, info.permission.value());
+                writer.writeln_colored(message.as_str(), Color::RED);
+                *result = LastCommandResult::Error(message);
+                return;
+            }
+            if let Some(usage) = &info.usage {
+                let (min, max) = usage_arity(usage);
+                let count = cmd.args().len();
+                if count < min || max.is_some_and(|max| count > max) {
+                    let message = format!("Usage: {usage}");
+                    writer.writeln_colored(message.as_str(), Color::YELLOW);
+                    *result = LastCommandResult::Error(message);
+                    return;
+                }
+            }
+            let args = split_name(&cmd.raw).1.trim().to_owned();
+            executed.send(CommandExecuted { name:name.to_string(), args });
+            // Typed with a different case when matching ignores it.
+            if cmd.name != **name {
+                cmd.name = name.to_string();
+            }
+            if config.profile {
+                // Run it from an exclusive command rather than `run_system` so the timing covers only the command's own system.
+                let id = info.id;
+                commands.add(move |world:&mut World| {
+                    let start = Instant::now();
+                    let _ = world.run_system(id);
+                    let took = start.elapsed().as_secs_f64() * 1000.0;
+                    world.run_system_once_with(format!("(took {took:.1}ms)"), |In(text):In<String>, mut writer:ConsoleWriter| {
+                        writer.writeln_colored(text, Color::GRAY);
+                    });
+                });
+            } else {
+                commands.run_system(info.id);
+            }
+            // After the command, so `last_result` still sees the previous one.
+            commands.add(finish_cmd);
+        } else {
+            // Never the name as typed, which may be an alias whose target wasn't found.
+            let typed = |name:&str| if config.case_insensitive { name.eq_ignore_ascii_case(call) } else { name == call };
+            let names = || map.keys().chain(aliases.keys()).map(|cmd| cmd.as_ref()).filter(|name| !typed(name));
+            let close = fuzzy_matches(names(), call, config.case_insensitive).first().copied()
+                .or_else(|| closest_by_edits(names(), call, config.case_insensitive));
+            match close {
+                Some(close) => writer.writeln_colored(format!("Command not found: {call}. Did you mean '{close}'?"), Color::RED),
+                None => writer.writeln_colored(format!("Command not found: {call}"), Color::RED),
+            }
+            not_found.send(CommandNotFound { name:call.to_owned() });
+            *result = LastCommandResult::NotFound(call.to_owned());
+        }
+    }
+}
+
+
+#[derive(States, Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+struct CommandLineCommandsTrigger(u16);
+
+/// A registered console command.
+#[derive(Debug, Clone)]
+pub struct CommandInfo {
+    pub id:SystemId,
+    /// Short explanation shown by `help`.
+    pub description:Option<Cow<'static, str>>,
+    /// Group `help` lists the command under, commands without one go under "General".
+    pub category:Option<Cow<'static, str>>,
+    /// Argument hint shown by `help <command>`, e.g. `teleport <x> <y>`.
+    pub usage:Option<Cow<'static, str>>,
+    /// Suggests arguments when Tab is pressed after the command name.
+    pub completer:Option<ArgCompleter>,
+    /// Lowest `sv_cheats` level the command runs at, see [`ConsolePlugin::set_cmd_permission`].
+    pub permission:PermissionLevel,
+}
+
+/// How privileged a command is. Commands above the level set by the `sv_cheats` cvar are refused:
+/// `sv_cheats 0` allows only [`PermissionLevel::User`] commands, `1` also cheats and `2` everything.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PermissionLevel {
+    #[default]
+    User,
+    Cheat,
+    Dev,
+}
+
+impl PermissionLevel {
+    /// The `sv_cheats` value that allows this level.
+    pub fn value(self) -> i32 {
+        self as i32
+    }
+
+    /// The highest level the `sv_cheats` cvar currently allows, [`PermissionLevel::User`] if it isn't set.
+    pub fn allowed(cvars:&Cvars) -> Self {
+        match cvars.get::<i32>(CHEATS_CVAR) {
+            Some(level) if level >= Self::Dev.value() => Self::Dev,
+            Some(level) if level >= Self::Cheat.value() => Self::Cheat,
+            _ => Self::User,
+        }
+    }
+}
+
+impl fmt::Display for PermissionLevel {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PermissionLevel::User => "user",
+            PermissionLevel::Cheat => "cheat",
+            PermissionLevel::Dev => "dev",
+        })
+    }
+}
+
+/// Cvar holding the highest [`PermissionLevel`] commands may have to run. Starts at 0 every run -
+/// [`ConsoleConfig::persist_cvars`] and `writeconfig` leave it out.
+const CHEATS_CVAR:&str = "sv_cheats";
+
+/// Tab completion for command arguments, see [`ConsolePlugin::add_cmd_with_completer`] and [`ConsolePlugin::add_cmd_typed`].
+#[derive(Clone)]
+pub struct ArgCompleter(Arc<CompleterFn>);
+
+type CompleterFn = dyn Fn(&[&str], &World) -> Vec<String> + Send + Sync;
+
+impl ArgCompleter {
+    pub fn new(completer:impl Fn(&[&str]) -> Vec<String> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(move |args, _| completer(args)))
+    }
+
+    /// Same as [`ArgCompleter::new`], but `completer` can also look at the world, e.g. for entity names.
+    pub fn with_world(completer:impl Fn(&[&str], &World) -> Vec<String> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(completer))
+    }
+
+    /// Candidates for the last of `args`, which is the one being typed and may be empty.
+    pub fn complete(&self, args:&[&str], world:&World) -> Vec<String> {
+        (self.0)(args, world)
+    }
+}
+
+impl fmt::Debug for ArgCompleter {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ArgCompleter")
+    }
+}
+
+impl CommandInfo {
+    fn new(id:SystemId) -> Self {
+        Self { id, description:None, category:None, usage:None, completer:None, permission:PermissionLevel::User }
+    }
+}
+
+/// Every registered command by name. Prefer its methods over the underlying map, which may change.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct CommandMap(HashMap<Cow<'static, str>, CommandInfo>);
+
+impl CommandMap {
+    /// Whether a command is registered under exactly `name`. Aliases don't count.
+    pub fn contains_command(&self, name:&str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Names of every registered command, sorted.
+    pub fn command_names(&self) -> Vec<&str> {
+        let mut names:Vec<&str> = self.0.keys().map(|name| name.as_ref()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn command(&self, name:&str) -> Option<&CommandInfo> {
+        self.0.get(name)
+    }
+}
+
+/// Alternative names for commands, see [`ConsolePlugin::add_alias`].
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct CommandAliases(HashMap<Cow<'static, str>, Cow<'static, str>>);
+
+impl CommandAliases {
+    /// Repeatedly replaces the first word of `line` while it names an alias. A target may hold several commands
+    /// separated by `;`, in which case the first of them is expanded further.
+    /// Returns `None` if `line` doesn't start with an alias, or the chain of alias names if they loop.
+    fn expand(&self, line:&str, case_insensitive:bool) -> Result<Option<String>, Vec<String>> {
+        let mut chain:Vec<String> = Vec::new();
+        let mut line = line.to_owned();
+        loop {
+            let segments = split_commands(&line);
+            let (first, later) = segments.split_first().map_or(("", &[][..]), |(first, later)| (*first, later));
+            let (name, rest) = split_name(first);
+            let Some((alias, target)) = find_name(self, name, case_insensitive) else {
+                return Ok((!chain.is_empty()).then_some(line));
+            };
+            let is_loop = chain.iter().any(|seen| seen == alias);
+            chain.push(alias.to_string());
+            if is_loop {
+                return Err(chain);
+            }
+            let expanded = if rest.is_empty() { target.to_string() } else { format!("{target} {rest}") };
+            line = std::iter::once(expanded.as_str()).chain(later.iter().copied()).collect::<Vec<_>>().join("; ");
+        }
+    }
+}
+
+/// Commands run when a key is pressed, set up with the `bind` command. Keys are only watched while the console is not open.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct KeyBindings(HashMap<KeyCode, String>);
+
+/// Every named [`KeyCode`], by its variant name.
+macro_rules! key_codes {
+    ($($key:ident),* $(,)?) => { &[$((stringify!($key), KeyCode::$key)),*] };
+}
+
+/// Full key names accepted by [`parse_keycode`], matched ignoring case.
+const KEY_CODES:&[(&str, KeyCode)] = key_codes![
+    Backquote, Backslash, BracketLeft, BracketRight, Comma, Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7,
+    Digit8, Digit9, Equal, IntlBackslash, IntlRo, IntlYen, KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK,
+    KeyL, KeyM, KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ, Minus, Period, Quote, Semicolon,
+    Slash, AltLeft, AltRight, Backspace, CapsLock, ContextMenu, ControlLeft, ControlRight, Enter, SuperLeft, SuperRight,
+    ShiftLeft, ShiftRight, Space, Tab, Convert, KanaMode, Lang1, Lang2, Lang3, Lang4, Lang5, NonConvert, Delete, End, Help,
+    Home, Insert, PageDown, PageUp, ArrowDown, ArrowLeft, ArrowRight, ArrowUp, NumLock, Numpad0, Numpad1, Numpad2, Numpad3,
+    Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, NumpadAdd, NumpadBackspace, NumpadClear, NumpadClearEntry,
+    NumpadComma, NumpadDecimal, NumpadDivide, NumpadEnter, NumpadEqual, NumpadHash, NumpadMemoryAdd, NumpadMemoryClear,
+    NumpadMemoryRecall, NumpadMemoryStore, NumpadMemorySubtract, NumpadMultiply, NumpadParenLeft, NumpadParenRight,
+    NumpadStar, NumpadSubtract, Escape, Fn, FnLock, PrintScreen, ScrollLock, Pause, BrowserBack, BrowserFavorites,
+    BrowserForward, BrowserHome, BrowserRefresh, BrowserSearch, BrowserStop, Eject, LaunchApp1, LaunchApp2, LaunchMail,
+    MediaPlayPause, MediaSelect, MediaStop, MediaTrackNext, MediaTrackPrevious, Power, Sleep, AudioVolumeDown,
+    AudioVolumeMute, AudioVolumeUp, WakeUp, Meta, Hyper, Turbo, Abort, Resume, Suspend, Again, Copy, Cut, Find, Open, Paste,
+    Props, Select, Undo, Hiragana, Katakana, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18,
+    F19, F20, F21, F22, F23, F24, F25, F26, F27, F28, F29, F30, F31, F32, F33, F34, F35,
+];
+
+/// Short names accepted alongside [`KEY_CODES`]. [`keycode_name`] prefers these, so `binds` lists `A` rather than `KeyA`.
+const KEY_ALIASES:&[(&str, KeyCode)] = &[
+    ("A", KeyCode::KeyA), ("B", KeyCode::KeyB), ("C", KeyCode::KeyC), ("D", KeyCode::KeyD), ("E", KeyCode::KeyE),
+    ("F", KeyCode::KeyF), ("G", KeyCode::KeyG), ("H", KeyCode::KeyH), ("I", KeyCode::KeyI), ("J", KeyCode::KeyJ),
+    ("K", KeyCode::KeyK), ("L", KeyCode::KeyL), ("M", KeyCode::KeyM), ("N", KeyCode::KeyN), ("O", KeyCode::KeyO),
+    ("P", KeyCode::KeyP), ("Q", KeyCode::KeyQ), ("R", KeyCode::KeyR), ("S", KeyCode::KeyS), ("T", KeyCode::KeyT),
+    ("U", KeyCode::KeyU), ("V", KeyCode::KeyV), ("W", KeyCode::KeyW), ("X", KeyCode::KeyX), ("Y", KeyCode::KeyY),
+    ("Z", KeyCode::KeyZ),
+    ("0", KeyCode::Digit0), ("1", KeyCode::Digit1), ("2", KeyCode::Digit2), ("3", KeyCode::Digit3), ("4", KeyCode::Digit4),
+    ("5", KeyCode::Digit5), ("6", KeyCode::Digit6), ("7", KeyCode::Digit7), ("8", KeyCode::Digit8), ("9", KeyCode::Digit9),
+    ("Up", KeyCode::ArrowUp), ("Down", KeyCode::ArrowDown), ("Left", KeyCode::ArrowLeft), ("Right", KeyCode::ArrowRight),
+    ("LShift", KeyCode::ShiftLeft), ("RShift", KeyCode::ShiftRight), ("LCtrl", KeyCode::ControlLeft), ("RCtrl", KeyCode::ControlRight),
+    ("LAlt", KeyCode::AltLeft), ("RAlt", KeyCode::AltRight), ("Esc", KeyCode::Escape), ("Del", KeyCode::Delete),
+];
+
+/// Parses a key name as used by `bind` and `unbind`, ignoring case.
+///
+/// Accepts every [`KeyCode`] variant name (`KeyA`, `Numpad5`, `ShiftLeft`, `F13`, ...) as well as short forms
+/// like `A`, `5`, `Up` or `LShift`. Returns `None` for anything else, including [`KeyCode::Unidentified`].
+pub fn parse_keycode(name:&str) -> Option<KeyCode> {
+    KEY_ALIASES.iter().chain(KEY_CODES)
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|(_, key)| *key)
+}
+
+/// The name [`parse_keycode`] reads back as `key`, using the short form where there is one.
+/// Returns `"Unidentified"` for [`KeyCode::Unidentified`], which has no name.
+pub fn keycode_name(key:KeyCode) -> &'static str {
+    KEY_ALIASES.iter().chain(KEY_CODES)
+        .find(|(_, named)| *named == key)
+        .map_or("Unidentified", |(name, _)| name)
+}
+
+/// Error printed by `bind` and `unbind` for a key name [`parse_keycode`] doesn't know.
+fn unknown_key(key_name:&str) -> String {
+    format!("Unknown key: {key_name}. Use names like A, 5, F1, Space, Numpad5 or ShiftLeft.")
+}
+
+/// Runs the commands bound to keys pressed this frame.
+fn run_binds(kbd:Res<ButtonInput<KeyCode>>, bindings:Res<KeyBindings>, mut pending:ResMut<PendingCommands>) {
+    for key in kbd.get_just_pressed() {
+        if let Some(cmd) = bindings.get(key) {
+            pending.push_back(cmd.clone());
+        }
+    }
+}
+
+/// Value of a console variable, see [`Cvars`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CvarValue {
+    F32(f32),
+    I32(i32),
+    Bool(bool),
+    String(String),
+}
+
+impl CvarValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CvarValue::F32(_) => "float",
+            CvarValue::I32(_) => "integer",
+            CvarValue::Bool(_) => "bool",
+            CvarValue::String(_) => "string",
+        }
+    }
+
+    /// Parses `text` as a value of the same type as `self`. Bools accept `true`/`false`, `1`/`0` and `on`/`off`.
+    pub fn parse_same(&self, text:&str) -> Result<CvarValue, String> {
+        let invalid = || format!("{text:?} is not a valid {}", self.type_name());
+        Ok(match self {
+            CvarValue::F32(_) => CvarValue::F32(text.parse().map_err(|_| invalid())?),
+            CvarValue::I32(_) => CvarValue::I32(text.parse().map_err(|_| invalid())?),
+            CvarValue::Bool(_) => CvarValue::Bool(match text.to_ascii_lowercase().as_str() {
+                "true" | "1" | "on" => true,
+                "false" | "0" | "off" => false,
+                _ => return Err(invalid()),
+            }),
+            CvarValue::String(_) => CvarValue::String(text.to_owned()),
+        })
+    }
+}
+
+impl fmt::Display for CvarValue {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CvarValue::F32(value) => value.fmt(f),
+            CvarValue::I32(value) => value.fmt(f),
+            CvarValue::Bool(value) => value.fmt(f),
+            CvarValue::String(value) => value.fmt(f),
+        }
+    }
+}
+
+impl From<f32> for CvarValue {
+    fn from(value:f32) -> Self { CvarValue::F32(value) }
+}
+
+impl From<i32> for CvarValue {
+    fn from(value:i32) -> Self { CvarValue::I32(value) }
+}
+
+impl From<bool> for CvarValue {
+    fn from(value:bool) -> Self { CvarValue::Bool(value) }
+}
+
+impl From<String> for CvarValue {
+    fn from(value:String) -> Self { CvarValue::String(value) }
+}
+
+impl From<&str> for CvarValue {
+    fn from(value:&str) -> Self { CvarValue::String(value.to_owned()) }
+}
+
+/// Types that can be read out of a [`CvarValue`] with [`Cvars::get`].
+pub trait FromCvar: Sized {
+    fn from_cvar(value:&CvarValue) -> Option<Self>;
+}
+
+impl FromCvar for f32 {
+    fn from_cvar(value:&CvarValue) -> Option<Self> {
+        if let CvarValue::F32(value) = value { Some(*value) } else { None }
+    }
+}
+
+impl FromCvar for i32 {
+    fn from_cvar(value:&CvarValue) -> Option<Self> {
+        if let CvarValue::I32(value) = value { Some(*value) } else { None }
+    }
+}
+
+impl FromCvar for bool {
+    fn from_cvar(value:&CvarValue) -> Option<Self> {
+        if let CvarValue::Bool(value) = value { Some(*value) } else { None }
+    }
+}
+
+impl FromCvar for String {
+    fn from_cvar(value:&CvarValue) -> Option<Self> {
+        if let CvarValue::String(value) = value { Some(value.clone()) } else { None }
+    }
+}
+
+fn persist_cvars_enabled(config:Res<ConsoleConfig>) -> bool {
+    config.persist_cvars
+}
+
+/// Sets the cvars listed in the cvars file. Cvars missing from it keep their defaults, unknown ones are skipped with a warning.
+fn load_cvars(config:Res<ConsoleConfig>, mut cvars:ResMut<Cvars>) {
+    let path = &config.cvars_file;
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            warn!("Starting with default cvars, couldn't read {}: {err}", path.display());
+            return;
+        },
+    };
+    for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let tokens = tokenize(line);
+        let (name, text) = match &tokens[..] {
+            [set, name, text] if set == "set" => (name, text),
+            _ => {
+                warn!("Skipping line {} of {}, expected `set <name> <value>`: {line}", number + 1, path.display());
+                continue;
+            },
+        };
+        let result = match cvars.get_value(name) {
+            _ if name == CHEATS_CVAR => Err(format!("{CHEATS_CVAR} isn't loaded, set it every run")),
+            Some(current) => current.parse_same(text),
+            None => Err(format!("Unknown cvar: {name}")),
+        };
+        if let Err(err) = result.and_then(|value| cvars.set(name, value)) {
+            warn!("Skipping line {} of {}: {err}", number + 1, path.display());
+        }
+    }
+}
+
+/// Wraps `text` in double quotes, escaping it so [`tokenize`] reads it back as one word.
+fn quote(text:&str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Appends a `set <name> <value>` line for every cvar, sorted by name.
+fn write_cvars(contents:&mut String, cvars:&Cvars) {
+    // Saving it would unlock cheats for every later run.
+    let mut all:Vec<_> = cvars.iter().filter(|(name, _)| *name != CHEATS_CVAR).collect();
+    all.sort_unstable_by_key(|(name, _)| *name);
+    for (name, value) in all {
+        let _ = match value {
+            CvarValue::String(text) => writeln!(contents, "set {name} {}", quote(text)),
+            value => writeln!(contents, "set {name} {value}"),
+        };
+    }
+}
+
+fn save_cvars(config:Res<ConsoleConfig>, cvars:Res<Cvars>, mut exit:EventReader<AppExit>) {
+    if exit.read().last().is_none() {
+        return;
+    }
+    let mut contents = String::new();
+    write_cvars(&mut contents, &cvars);
+    let path = &config.cvars_file;
+    let result = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(path, contents));
+    if let Err(err) = result {
+        warn!("Couldn't save cvars to {}: {err}", path.display());
+    }
+}
+
+/// Console variables - named values that can be changed with `set <name> <value>` and read with `get <name>`.
+/// Register them with [`ConsolePlugin::register_cvar`]; a cvar keeps the type of its default value.
+/// Restrict the values further with [`ConsolePlugin::set_cvar_validator`].
+#[derive(Resource, Default)]
+pub struct Cvars {
+    values:HashMap<Cow<'static, str>, CvarValue>,
+    validators:HashMap<Cow<'static, str>, Arc<CvarValidator>>,
+}
+
+type CvarValidator = dyn Fn(&CvarValue) -> Result<(), String> + Send + Sync;
+
+impl Cvars {
+    /// Reads cvar `name`, or `None` if it doesn't exist or isn't a `T`.
+    pub fn get<T:FromCvar>(&self, name:&str) -> Option<T> {
+        self.values.get(name).and_then(T::from_cvar)
+    }
+
+    pub fn get_value(&self, name:&str) -> Option<&CvarValue> {
+        self.values.get(name)
+    }
+
+    /// Changes an existing cvar. Fails if `name` isn't registered, `value` has a different type
+    /// or the cvar's validator rejects it - the cvar keeps its value then.
+    pub fn set(&mut self, name:&str, value:impl Into<CvarValue>) -> Result<(), String> {
+        let value = value.into();
+        let current = self.values.get_mut(name).ok_or_else(|| format!("Unknown cvar: {name}"))?;
+        if std::mem::discriminant(current) != std::mem::discriminant(&value) {
+            return Err(format!("{name} is a {}, not a {}", current.type_name(), value.type_name()));
+        }
+        if let Some(validate) = self.validators.get(name) {
+            validate(&value)?;
+        }
+        *current = value;
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CvarValue)> {
+        self.values.iter().map(|(name, value)| (name.as_ref(), value))
+    }
+}
+
+/// Name of the console added with [`ConsolePlugin`] itself rather than [`ConsolePlugin::new`].
+pub const DEFAULT_CONSOLE:&str = "default";
+
+/// Every console of the app, see [`ConsolePlugin::new`]. The active console is the one shown and typed into. Its
+/// commands, output, history, settings and theme are the usual resources like [`CommandMap`] and [`ConsoleOutput`],
+/// while those of the other consoles are parked here until they become active.
+#[derive(Resource)]
+pub struct ConsoleInstances {
+    active:Cow<'static, str>,
+    parked:HashMap<Cow<'static, str>, ParkedConsole>,
+}
+
+impl ConsoleInstances {
+    /// Name of the console that is shown and typed into.
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    /// Names of every console, the active one first.
+    pub fn names(&self) -> Vec<&str> {
+        let mut parked:Vec<&str> = self.parked.keys().map(|id| id.as_ref()).collect();
+        parked.sort_unstable();
+        std::iter::once(self.active.as_ref()).chain(parked).collect()
+    }
+
+    fn contains(&self, id:&str) -> bool {
+        self.active == id || self.parked.contains_key(id)
+    }
+}
+
+/// The resources of a console that isn't the active one.
+struct ParkedConsole {
+    id:Cow<'static, str>,
+    config:ConsoleConfig,
+    theme:ConsoleTheme,
+    map:CommandMap,
+    aliases:CommandAliases,
+    output:ConsoleOutput,
+    history:CommandHistory,
+    input:InputLine,
+    suggestions:Suggestions,
+    pending:PendingCommands,
+    result:LastCommandResult,
+}
+
+impl ParkedConsole {
+    fn new(id:Cow<'static, str>, config:ConsoleConfig, theme:ConsoleTheme, max_lines:usize) -> Self {
+        Self { id, config, theme, map:default(), aliases:default(), output:ConsoleOutput::with_max_lines(max_lines), history:default(),
+            input:default(), suggestions:default(), pending:default(), result:default() }
+    }
+
+    /// Trades places with the console in `world`. Unless `changed`, systems won't see its resources as changed,
+    /// as the console only stands in for the active one for a moment.
+    fn swap(&mut self, world:&mut World, changed:bool) {
+        fn swap_resource<T:Resource>(world:&mut World, parked:&mut T, changed:bool) {
+            let mut active = world.resource_mut::<T>();
+            if changed {
+                std::mem::swap(&mut *active, parked);
+            } else {
+                std::mem::swap(active.bypass_change_detection(), parked);
+            }
+        }
+        // Parked output isn't stamped with the time, so catch up.
+        self.output.now = world.resource::<ConsoleOutput>().now;
+        swap_resource(world, &mut self.config, changed);
+        swap_resource(world, &mut self.theme, changed);
+        swap_resource(world, &mut self.map, changed);
+        swap_resource(world, &mut self.aliases, changed);
+        swap_resource(world, &mut self.output, changed);
+        swap_resource(world, &mut self.history, changed);
+        swap_resource(world, &mut self.input, changed);
+        swap_resource(world, &mut self.suggestions, changed);
+        swap_resource(world, &mut self.pending, changed);
+        swap_resource(world, &mut self.result, changed);
+    }
+}
+
+/// Puts the parked console `id` in place of the active one. Hand the returned active console to [`repark`] to undo it.
+fn unpark(world:&mut World, id:&str) -> Option<ParkedConsole> {
+    let mut parked = world.get_resource_mut::<ConsoleInstances>()?.parked.remove(id)?;
+    parked.swap(world, false);
+    Some(parked)
+}
+
+fn repark(world:&mut World, mut parked:ParkedConsole) {
+    parked.swap(world, false);
+    world.resource_mut::<ConsoleInstances>().parked.insert(parked.id.clone(), parked);
+}
+
+/// Makes the console `id` the active one, shown and typed into, and parks the current one with its output, history
+/// and input line as they are. It stays open or closed, unlike when switching with the other console's toggle key.
+/// Returns false if there is no console `id`.
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// fn show_server_console(mut commands:Commands) {
+///     commands.add(|world:&mut World| {
+///         activate_console(world, "server");
+///     });
+/// }
+/// ```
+pub fn activate_console(world:&mut World, id:&str) -> bool {
+    let Some(instances) = world.get_resource::<ConsoleInstances>() else { return false };
+    if instances.active == id {
+        return true;
+    }
+    let Some(mut parked) = world.resource_mut::<ConsoleInstances>().parked.remove(id) else { return false };
+    parked.swap(world, true);
+    let mut instances = world.resource_mut::<ConsoleInstances>();
+    parked.id = std::mem::replace(&mut instances.active, parked.id);
+    instances.parked.insert(parked.id.clone(), parked);
+    world.run_system_once(apply_scrollback);
+    // The UI is laid out for the console's anchor and size, so lay it out anew.
+    let ui:Vec<Entity> = world.query_filtered::<Entity, With<ConsoleTag>>().iter(world).collect();
+    if !ui.is_empty() {
+        for entity in ui {
+            despawn_with_children_recursive(world, entity);
+        }
+        world.run_system_once(setup_ui);
+        world.resource_mut::<ConsoleAnimation>().set_changed();
+    }
+    true
+}
+
+fn has_parked_consoles(instances:Res<ConsoleInstances>) -> bool {
+    !instances.parked.is_empty()
+}
+
+/// Switches to the console whose toggle key was pressed. A closed console then opens as usual, while an open one is
+/// swapped for the other right away.
+fn switch_console(world:&mut World) {
+    if !world.resource::<InputLine>().preedit.is_empty() {
+        return;
+    }
+    let kbd = world.resource::<ButtonInput<KeyCode>>();
+    let own_key = world.resource::<ConsoleConfig>().toggle_key;
+    let Some(id) = world.resource::<ConsoleInstances>().parked.iter()
+        .find(|(_, parked)| parked.config.toggle_key != own_key && kbd.just_pressed(parked.config.toggle_key))
+        .map(|(id, _)| id.clone()) else { return };
+    let state = *world.resource::<State<ConsoleState>>().get();
+    if matches!(state, ConsoleState::AnimatingOpen | ConsoleState::AnimatingClosed) {
+        return;
+    }
+    activate_console(world, &id);
+    if state == ConsoleState::Open {
+        // The key is now the active console's toggle key and would close it again.
+        world.run_system_once(consume_toggle_key);
+    }
+}
+
+/// Saves the history of every parked console on exit, like [`save_history`] does for the active one.
+fn save_parked_histories(world:&mut World) {
+    let ids:Vec<_> = world.resource::<ConsoleInstances>().parked.iter()
+        .filter(|(_, parked)| parked.config.persist_history)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in ids {
+        if let Some(parked) = unpark(world, &id) {
+            world.run_system_once(save_history);
+            repark(world, parked);
+        }
+    }
+}
+
+/// Gives a console other than the default one its own history file, unless one was set.
+fn name_history_file(config:&mut ConsoleConfig, id:&str) {
+    if id != DEFAULT_CONSOLE && config.history_file == ConsoleConfig::default().history_file {
+        config.history_file = default_data_file(&format!("console_history_{id}.txt"));
+    }
+}
+
+/// Creates a drop down console that can be used to call one-shot systems
+/// To add system as a callable command - use 
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// # let mut app = App::new();
+/// # fn your_system() {}
+/// ConsolePlugin::add_cmd(&mut app, "run_me", your_system);
+/// ```
+/// The command arguments will be available to your system through [`CommandArgs`] resource.
+/// Warning - the key "run_me" as well as [`SystemId`] generated from your system will be put in a public resource
+/// so other plugins can mess with its keys and values, resuling in potentially unexpected system calls.
+/// Inside the called system you can print to the console output and read its state through [`Console`]
+/// ```
+/// # use console::*;
+/// fn your_system(mut console:Console) {
+///     let args = console.args().map(|args| args.args().join(" ")).unwrap_or_default();
+///     console.writeln(format!("done with {args}"));
+/// }
+/// ```
+/// Never query the [`Text`] of [`ConsoleOutputTag`] to print - the console owns it and rewrites it whenever the output changes.
+/// Commands that can fail should return a `Result` and be added with [`ConsolePlugin::add_cmd_result`]
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// # let mut app = App::new();
+/// fn teleport(args:Res<CommandArgs>) -> Result<(), String> {
+///     let (x, y):(f32, f32) = args.parse_args().map_err(|e| e.to_string())?;
+///     # let _ = (x, y);
+///     Ok(())
+/// }
+/// ConsolePlugin::add_cmd_result(&mut app, "teleport", teleport);
+/// ```
+/// The console is toggled with the backquote key by default. Settings can be changed with the builder methods,
+/// which fill in the [`ConsoleConfig`] and [`ConsoleTheme`] resources when the plugin is added:
+/// ```
+/// # use bevy::prelude::*;
+/// # use console::*;
+/// # let mut app = App::new();
+/// app.add_plugins(ConsolePlugin::default().toggle_key(KeyCode::F1).height(0.5).move_speed(200.0).case_insensitive(true));
+/// ```
+/// `add_plugins(ConsolePlugin)` adds it with the defaults, or with resources you inserted yourself.
+/// It relies on the input, window and time plugins of `DefaultPlugins` and panics on startup naming any that are
+/// missing. [`ConsolePlugin::headless`] needs none of them, so it also runs under `MinimalPlugins` in tests.
+///
+/// An app can have several consoles, e.g. one for client and one for server commands, each added with
+/// [`ConsolePlugin::new`]. Adding the same console twice panics.
+#[derive(Default, Clone)]
+pub struct ConsolePlugin {
+    id:Option<Cow<'static, str>>,
+    config:Option<ConsoleConfig>,
+    theme:Option<ConsoleTheme>,
+    headless:bool,
+}
+
+/// Lets `add_plugins(ConsolePlugin)` keep working now that the plugin has settings.
+#[allow(non_upper_case_globals)]
+pub const ConsolePlugin:ConsolePlugin = ConsolePlugin { id:None, config:None, theme:None, headless:false };
+
+macro_rules! config_setters {
+    ($($(#[$doc:meta])* $field:ident: $ty:ty),* $(,)?) => {$(
+        $(#[$doc])*
+        pub fn $field(mut self, $field:$ty) -> Self {
+            self.config_mut().$field = $field;
+            self
+        }
+    )*};
+}
+
+impl ConsolePlugin {
+    fn config_mut(&mut self) -> &mut ConsoleConfig {
+        self.config.get_or_insert_with(ConsoleConfig::default)
+    }
+
+    /// The console without UI or keyboard handling, e.g. for a dedicated server. Commands, aliases, cvars, history
+    /// and scripts work as usual; submit lines with [`CommandSubmitted`] or [`PendingCommands`] and read what they
+    /// print from [`ConsolePrinted`] events or [`ConsoleOutput`].
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use console::*;
+    /// fn forward_output(mut printed:EventReader<ConsolePrinted>) {
+    ///     for ConsolePrinted { text, .. } in printed.read() {
+    ///         print!("{text}");
+    ///     }
+    /// }
+    /// # let mut app = App::new();
+    /// app.add_plugins(ConsolePlugin::headless()).add_systems(Update, forward_output);
+    /// ```
+    pub fn headless() -> Self {
+        Self { headless:true, ..Self::default() }
+    }
+
+    /// A console named `id`, for apps with more than one:
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use console::*;
+    /// # let mut app = App::new();
+    /// # fn kick() {}
+    /// app.add_plugins((ConsolePlugin, ConsolePlugin::new("server").toggle_key(KeyCode::F2).prompt("server> ".into())));
+    /// ConsolePlugin::with_console(&mut app, "server", |app| ConsolePlugin::add_cmd(app, "kick", kick));
+    /// ```
+    /// Every console has its own commands, aliases, output, history, settings and theme, and pressing its toggle key
+    /// switches to it - opening it if it's closed, or in place of the console that is open. Only the active console
+    /// is shown, and [`ConsoleState`] and resources like [`CommandMap`] or [`ConsoleOutput`] are those of the active
+    /// console. [`ConsoleInstances`] keeps the others meanwhile, and lines queued for them still run.
+    /// Cvars, key bindings, the command filter and the log file are shared.
+    ///
+    /// Plain [`ConsolePlugin`] is named [`DEFAULT_CONSOLE`]. The first console added starts out active and decides
+    /// whether there is a UI, see [`ConsolePlugin::headless`]. The history of the other consoles is kept in a file
+    /// named after them unless [`ConsoleConfig::history_file`] is set.
+    pub fn new(id:impl Into<Cow<'static, str>>) -> Self {
+        Self { id:Some(id.into()), ..Self::default() }
+    }
+
+    /// Runs `f` with the console `id` standing in for the active one, so functions like [`ConsolePlugin::add_cmd`]
+    /// and [`ConsolePlugin::execute`] apply to it. Returns `None` if there is no console `id` yet.
+    pub fn with_console<R>(app:&mut App, id:&str, f:impl FnOnce(&mut App) -> R) -> Option<R> {
+        if app.world.get_resource::<ConsoleInstances>()?.active == id {
+            return Some(f(app));
+        }
+        let parked = unpark(&mut app.world, id)?;
+        let result = f(app);
+        repark(&mut app.world, parked);
+        Some(result)
+    }
+
+    /// Adds a console after the first one, parked until it's activated.
+    fn add_parked(&self, app:&mut App, id:Cow<'static, str>) {
+        let mut config = self.config.clone().unwrap_or_default();
+        name_history_file(&mut config, &id);
+        let max_lines = app.world.resource::<ConsoleOutput>().max_lines();
+        let parked = ParkedConsole::new(id.clone(), config, self.theme.clone().unwrap_or_default(), max_lines);
+        app.world.resource_mut::<ConsoleInstances>().parked.insert(id.clone(), parked);
+        Self::with_console(app, &id, |app| {
+            Self::register_builtin_cmds(app);
+            if app.world.resource::<ConsoleConfig>().persist_history {
+                app.world.run_system_once(load_history);
+            }
+        });
+    }
+
+    /// Replaces every setting at once.
+    pub fn config(mut self, config:ConsoleConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Sets the console colors and font.
+    pub fn theme(mut self, theme:ConsoleTheme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    /// Sets [`ConsoleTheme::background`], e.g. to a translucent color. Backdrop blur isn't supported by Bevy UI.
+    pub fn background(mut self, color:Color) -> Self {
+        self.theme.get_or_insert_with(ConsoleTheme::default).background = color;
+        self
+    }
+
+    /// Console height as a fraction of the window, e.g. `0.5` for half of it. See [`ConsoleConfig::height_percent`].
+    pub fn height(mut self, fraction:f32) -> Self {
+        self.config_mut().height_percent = fraction * 100.;
+        self
+    }
+
+    config_setters! {
+        /// See [`ConsoleConfig::toggle_key`].
+        toggle_key: KeyCode,
+        /// See [`ConsoleConfig::anchor`].
+        anchor: ConsoleAnchor,
+        /// See [`ConsoleConfig::move_speed`].
+        move_speed: f32,
+        /// See [`ConsoleConfig::easing`].
+        easing: ConsoleEasing,
+        /// See [`ConsoleConfig::capture_input`].
+        capture_input: bool,
+        /// See [`ConsoleConfig::case_insensitive`].
+        case_insensitive: bool,
+        /// See [`ConsoleConfig::log_level`].
+        log_level: Level,
+        /// See [`ConsoleConfig::persist_history`].
+        persist_history: bool,
+        /// See [`ConsoleConfig::history_file`].
+        history_file: PathBuf,
+        /// See [`ConsoleConfig::cursor_blink_interval`].
+        cursor_blink_interval: f32,
+        /// See [`ConsoleConfig::max_suggestions`].
+        max_suggestions: usize,
+        /// See [`ConsoleConfig::echo_input`].
+        echo_input: bool,
+        /// See [`ConsoleConfig::profile`].
+        profile: bool,
+        /// See [`ConsoleConfig::max_input_len`].
+        max_input_len: Option<usize>,
+        /// See [`ConsoleConfig::timestamps`].
+        timestamps: bool,
+        /// See [`ConsoleConfig::pause_on_open`].
+        pause_on_open: bool,
+        /// See [`ConsoleConfig::key_repeat_delay`].
+        key_repeat_delay: f32,
+        /// See [`ConsoleConfig::key_repeat_interval`].
+        key_repeat_interval: f32,
+        /// See [`ConsoleConfig::max_completions`].
+        max_completions: usize,
+        /// See [`ConsoleConfig::game_version`].
+        game_version: Option<String>,
+        /// See [`ConsoleConfig::prompt`].
+        prompt: Cow<'static, str>,
+        /// See [`ConsoleConfig::wheel_scroll_lines`].
+        wheel_scroll_lines: usize,
+        /// See [`ConsoleConfig::persist_cvars`].
+        persist_cvars: bool,
+        /// See [`ConsoleConfig::cvars_file`].
+        cvars_file: PathBuf,
+        /// See [`ConsoleConfig::max_commands_per_frame`].
+        max_commands_per_frame: usize,
+        /// See [`ConsoleConfig::close_on_submit`].
+        close_on_submit: bool,
+    }
+}
+
+impl ConsolePlugin{
+    /// Registers `system` as the command `name` and returns its [`SystemId`].
+    /// If `name` is already taken, a warning is logged, the existing command is kept and `None` is returned.
+    pub fn add_cmd<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, system: S ) -> Option<SystemId>
+where
+    S: IntoSystem<(), (), M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let id = world.register_system(system);
+            CommandInfo::new(id)
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd`], but `help` will list `description` next to the command name.
+    pub fn add_cmd_with_help<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, description:impl Into<Cow<'static, str>>, system: S ) -> Option<SystemId>
+where
+    S: IntoSystem<(), (), M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let id = world.register_system(system);
+            CommandInfo { description:Some(description.into()), ..CommandInfo::new(id) }
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd`], but for systems returning `Result<(), String>`.
+    /// An `Err` message is printed with [`ConsoleWriter::writeln_error`].
+    pub fn add_cmd_result<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, system: S ) -> Option<SystemId>
+where
+    S: IntoSystem<(), Result<(), String>, M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let id = world.register_system(system.pipe(report_cmd_error));
+            CommandInfo::new(id)
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd`], but the system receives everything after the command name as its input
+    /// instead of reading [`CommandArgs`]:
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use console::*;
+    /// # let mut app = App::new();
+    /// fn say(In(text):In<String>, mut writer:ConsoleWriter) {
+    ///     writer.writeln(text);
+    /// }
+    /// ConsolePlugin::add_cmd_in(&mut app, "say", say);
+    /// ```
+    pub fn add_cmd_in<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, system: S ) -> Option<SystemId>
+where
+    S: IntoSystem<String, (), M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let id = world.register_system(cmd_input.pipe(system));
+            CommandInfo::new(id)
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd_with_help`], but `help` will list the command under `category`,
+    /// and `help <category>` will list just that group.
+    pub fn add_cmd_with_category<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, category:impl Into<Cow<'static, str>>, description:impl Into<Cow<'static, str>>, system: S ) -> Option<SystemId>
+where
+    S: IntoSystem<(), (), M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let id = world.register_system(system);
+            CommandInfo { description:Some(description.into()), category:Some(category.into()), ..CommandInfo::new(id) }
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd_with_help`], but `help <command>` will also print `usage`, e.g. `"teleport <x> <y>"`.
+    /// The console checks the argument count against it and prints the usage instead of running the command when
+    /// it doesn't match: `<arg>` is required, `[arg]` optional, and `<args...>` or `[args...]` takes any number.
+    pub fn add_cmd_with_usage<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, description:impl Into<Cow<'static, str>>, usage:impl Into<Cow<'static, str>>, system: S ) -> Option<SystemId>
+where
+    S: IntoSystem<(), (), M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let id = world.register_system(system);
+            CommandInfo { description:Some(description.into()), usage:Some(usage.into()), ..CommandInfo::new(id) }
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd`], but pressing Tab after the command name calls `completer` with the arguments
+    /// typed so far - the last one being the partial argument, possibly empty. The returned candidates are filtered
+    /// by that partial argument, so the completer can simply return every valid value.
+    pub fn add_cmd_with_completer<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, system: S, completer:impl Fn(&[&str]) -> Vec<String> + Send + Sync + 'static) -> Option<SystemId>
+where
+    S: IntoSystem<(), (), M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let id = world.register_system(system);
+            CommandInfo { completer:Some(ArgCompleter::new(completer)), ..CommandInfo::new(id) }
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd`], but the arguments are parsed into `A`, a tuple of [`ConsoleArg`] types,
+    /// which the system receives as its input. Tab completes each argument with [`ConsoleArg::complete`], and
+    /// arguments that don't parse are reported as an error without running the system:
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use console::*;
+    /// # let mut app = App::new();
+    /// fn teleport(In((x, y)):In<(f32, f32)>, mut writer:ConsoleWriter) {
+    ///     writer.writeln(format!("Teleporting to {x}, {y}"));
+    /// }
+    /// ConsolePlugin::add_cmd_typed(&mut app, "teleport", teleport);
+    /// ```
+    pub fn add_cmd_typed<A, M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, system: S ) -> Option<SystemId>
+where
+    A: ConsoleArgs + 'static,
+    S: IntoSystem<A, (), M> + 'static,
+    {
+        Self::insert_cmd(app, name.into(), |world| {
+            let typed = world.register_system(system);
+            let report = world.register_system(report_cmd_error);
+            let id = world.register_system(move |world:&mut World| {
+                let parsed = A::parse_args(&world.resource::<CommandArgs>().args());
+                let result = match parsed {
+                    Ok(args) => world.run_system_with_input(typed, args).map_err(|err| err.to_string()),
+                    Err(err) => Err(err.to_string()),
+                };
+                // Only fails if the command removed itself.
+                let _ = world.run_system_with_input(report, result);
+            });
+            let completer = ArgCompleter::with_world(|args, world| {
+                let index = args.len().saturating_sub(1);
+                A::complete_arg(index, args.last().copied().unwrap_or_default(), world)
+            });
+            CommandInfo { completer:Some(completer), ..CommandInfo::new(id) }
+        })
+    }
+
+    /// Same as [`ConsolePlugin::add_cmd`], but panics if `name` is already taken, to catch conflicts at startup.
+    pub fn add_cmd_unique<M, S>(app:&mut App, name:impl Into<Cow<'static, str>>, system: S ) -> SystemId
+where
+    S: IntoSystem<(), (), M> + 'static,
+    {
+        let name = name.into();
+        if app.world.get_resource::<CommandMap>().is_some_and(|map| map.contains_key(&name)) {
+            panic!("Console command {name} is already registered");
+        }
+        Self::add_cmd(app, name, system).expect("name was checked to be free")
+    }
+
+    /// Registers the system built by `make` unless `name` is taken - the existing command is kept then,
+    /// so no system is leaked.
+    fn insert_cmd(app:&mut App, name:Cow<'static, str>, make:impl FnOnce(&mut World) -> CommandInfo) -> Option<SystemId> {
+        app.world.init_resource::<CommandMap>(); // Calling this just in case someone adds systems before registering the plugin.
+        if app.world.resource::<CommandMap>().contains_key(&name) {
+            warn!("Console command {name} is already registered, ignoring the new one. Call ConsolePlugin::remove_cmd first to replace it.");
+            return None;
+        }
+        let info = make(&mut app.world);
+        let id = info.id;
+        app.world.resource_mut::<CommandMap>().insert(name, info);
+        Some(id)
+    }
+
+    /// Makes `alias` run `target`. The target may include arguments - with `add_alias(app, "god", "set god_mode")`
+    /// typing `god true` runs `set god_mode true`. Returns the previous target of `alias` if there was one.
+    pub fn add_alias(app:&mut App, alias:impl Into<Cow<'static, str>>, target:impl Into<Cow<'static, str>>) -> Option<Cow<'static, str>> {
+        app.world.init_resource::<CommandAliases>();
+        app.world.resource_mut::<CommandAliases>().insert(alias.into(), target.into())
+    }
+
+    /// Adds a console variable with a `default` value, replacing and returning the previous one if `name` was taken.
+    pub fn register_cvar(app:&mut App, name:impl Into<Cow<'static, str>>, default:impl Into<CvarValue>) -> Option<CvarValue> {
+        app.world.init_resource::<Cvars>();
+        app.world.resource_mut::<Cvars>().values.insert(name.into(), default.into())
+    }
+
+    /// Makes `set` and [`Cvars::set`] refuse values of cvar `name` that `validator` returns an error for,
+    /// which is printed instead. Replaces any previous validator of `name`. The default value isn't checked.
+    pub fn set_cvar_validator(app:&mut App, name:impl Into<Cow<'static, str>>, validator:impl Fn(&CvarValue) -> Result<(), String> + Send + Sync + 'static) {
+        app.world.init_resource::<Cvars>();
+        app.world.resource_mut::<Cvars>().validators.insert(name.into(), Arc::new(validator));
+    }
+
+    /// Calls `filter` with the name and arguments of every command before it runs, after aliases are expanded.
+    /// The name is the registered one if the command exists. Replaces any previous filter.
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use console::*;
+    /// # let mut app = App::new();
+    /// ConsolePlugin::set_command_filter(&mut app, |name, _args| match name {
+    ///     "god" | "noclip" => Filter::Deny(format!("{name} is disabled in this build")),
+    ///     _ => Filter::Allow,
+    /// });
+    /// ```
+    pub fn set_command_filter(app:&mut App, filter:impl Fn(&str, &[&str]) -> Filter + Send + Sync + 'static) {
+        app.world.insert_resource(CommandFilter(Some(Arc::new(filter))));
+    }
+
+    /// Makes the command `name` refuse to run unless the `sv_cheats` cvar allows `level`, e.g. `set sv_cheats 1`
+    /// for [`PermissionLevel::Cheat`]. Commands are [`PermissionLevel::User`] until set otherwise.
+    /// Returns false if no such command exists.
+    pub fn set_cmd_permission(app:&mut App, name:&str, level:PermissionLevel) -> bool {
+        let Some(mut map) = app.world.get_resource_mut::<CommandMap>() else { return false };
+        let Some(info) = map.get_mut(name) else { return false };
+        info.permission = level;
+        true
+    }
+
+    /// Unregisters the command `name` and returns its [`SystemId`], or `None` if no such command exists.
+    /// The one-shot system itself stays registered in the world - call [`World::remove_system`] if you no longer need it.
+    pub fn remove_cmd(app:&mut App, name:&str) -> Option<SystemId> {
+        app.world.get_resource_mut::<CommandMap>()?.remove(name).map(|info| info.id)
+    }
+
+    /// Runs `line` right away, along with anything it queues with [`PendingCommands`], and returns what it printed.
+    /// The output is also kept in the console as usual, and commands see the console output printed before. No frame has to pass and no UI is needed, so this is handy
+    /// for testing commands:
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use console::*;
+    /// fn spawn(args:Res<CommandArgs>, mut writer:ConsoleWriter) {
+    ///     writer.writeln(format!("spawned at {}", args.args().join(" ")));
+    /// }
+    /// let mut app = App::new();
+    /// app.add_plugins(ConsolePlugin);
+    /// ConsolePlugin::add_cmd(&mut app, "spawn", spawn);
+    /// let output = ConsolePlugin::execute(&mut app, "spawn 1 2");
+    /// assert!(output.contains("spawned"));
+    /// ```
+    /// Requires the plugin to be added.
+    pub fn execute(app:&mut App, line:impl Into<String>) -> String {
+        let world = &mut app.world;
+        let start = world.resource::<ConsoleOutput>().end();
+        world.resource_mut::<PendingCommands>().push_back(line.into());
+        while let Some(line) = world.resource_mut::<PendingCommands>().pop_front() {
+            run_first_cmd(world, &line);
+        }
+        world.resource::<ConsoleOutput>().text_since(start)
+    }
+}
+
+/// The arguments of the running command, passed on to systems added with [`ConsolePlugin::add_cmd_in`].
+fn cmd_input(args:Res<CommandArgs>) -> String {
+    split_name(&args.raw).1.trim().to_owned()
+}
+
+fn report_cmd_error(In(result):In<Result<(), String>>, args:Option<Res<CommandArgs>>, mut writer:ConsoleWriter, mut error:ResMut<CommandError>) {
+    if let Err(message) = result {
+        // No arguments when the system is run directly rather than by the console
+        writer.writeln_error(args.as_ref().map_or("command", |args| args.name()), &message);
+        error.0 = Some(message);
+    }
+}
+
+const DEFAULT_CATEGORY:&str = "General";
+
+fn help(args:Res<CommandArgs>, map:Res<CommandMap>, aliases:Res<CommandAliases>, config:Res<ConsoleConfig>, mut writer:ConsoleWriter) {
+    let mut only = args.args().first().copied();
+    let expanded;
+    // An alias shows what it stands for, then the help of the command it runs.
+    if let Some((alias, target)) = only.and_then(|only| find_name(&aliases, only, config.case_insensitive)) {
+        writer.writeln(format!("{alias} -> {target}"));
+        expanded = match aliases.expand(alias, config.case_insensitive) {
+            Ok(line) => line.unwrap_or_default(),
+            Err(chain) => {
+                writer.writeln_colored(format!("Alias loop detected: {}", chain.join(" -> ")), Color::RED);
+                return;
+            },
+        };
+        let name = split_commands(&expanded).first().map_or("", |first| split_name(first).0);
+        if find_name(&map, name, config.case_insensitive).is_none() {
+            writer.writeln_colored(format!("No such command: {name}"), Color::RED);
+            return;
+        }
+        only = Some(name);
+    }
+    if let Some((name, info)) = only.and_then(|only| find_name(&map, only, config.case_insensitive)) {
+        writer.writeln_colored(name.as_ref(), Color::YELLOW);
+        writer.writeln(info.description.as_deref().unwrap_or("No description"));
+        if let Some(usage) = &info.usage {
+            writer.writeln(format!("Usage: {usage}"));
+        }
+        if info.permission > PermissionLevel::User {
+            writer.writeln(format!("Requires {CHEATS_CVAR} {}", info.permission.value()));
+        }
+        return;
+    }
+    let mut cmds:Vec<_> = map.iter()
+        .map(|(name, info)| (info.category.as_deref().unwrap_or(DEFAULT_CATEGORY), name, info))
+        .filter(|(category, _, _)| only.is_none_or(|only| category.eq_ignore_ascii_case(only)))
+        .collect();
+    if cmds.is_empty() {
+        if let Some(only) = only {
+            writer.writeln_colored(format!("No such command: {only}"), Color::RED);
+            return;
+        }
+    }
+    // General first, then the other categories alphabetically.
+    cmds.sort_unstable_by_key(|(category, name, _)| (*category != DEFAULT_CATEGORY, *category, *name));
+    // One table for every category so the descriptions line up across them.
+    let rows:Vec<Vec<String>> = cmds.iter().map(|(_, name, info)| match &info.description {
+        Some(description) => vec![format!("  {name}"), "-".to_owned(), description.to_string()],
+        None => vec![format!("  {name}")],
+    }).collect();
+    let mut current = None;
+    for ((category, _, _), line) in cmds.iter().zip(format_table(&rows)) {
+        if current != Some(category) {
+            writer.writeln_colored(format!("{category}:"), Color::YELLOW);
+            current = Some(category);
+        }
+        writer.writeln(line);
+    }
+    if only.is_some() {
+        return;
+    }
+    if !aliases.is_empty() {
+        writer.writeln_colored("Aliases:", Color::YELLOW);
+        let mut aliases:Vec<_> = aliases.iter().collect();
+        aliases.sort_unstable();
+        for (alias, target) in aliases {
+            writer.writeln(format!("{alias} -> {target}"));
+        }
+    }
+}
+
+fn last_result(result:Res<LastCommandResult>, mut writer:ConsoleWriter) {
+    match *result {
+        LastCommandResult::Ok => writer.writeln(result.to_string()),
+        _ => writer.writeln_colored(result.to_string(), Color::RED),
+    }
+}
+
+const REPEAT_CMD:&str = "!!";
+
+/// How deep [`repeats`] follows aliases that expand to further aliases in later `;` segments.
+const REPEAT_ALIAS_DEPTH:usize = 8;
+
+/// Whether running `line` runs `!!`, directly or through aliases. Aliases nested deeper than [`REPEAT_ALIAS_DEPTH`]
+/// count as repeating, since they most likely expand to themselves.
+fn repeats(line:&str, aliases:&CommandAliases, case_insensitive:bool, depth:usize) -> bool {
+    split_commands(line).into_iter().any(|segment| match aliases.expand(segment, case_insensitive) {
+        Ok(Some(expanded)) => depth == REPEAT_ALIAS_DEPTH || repeats(&expanded, aliases, case_insensitive, depth + 1),
+        Ok(None) => split_name(segment).0 == REPEAT_CMD,
+        // Fails to run anyway.
+        Err(_) => false,
+    })
+}
+
+/// Queues the newest history entry again, skipping `!!` itself since a typed `!!` is in history by the time it runs.
+fn repeat(history:Res<CommandHistory>, aliases:Res<CommandAliases>, config:Res<ConsoleConfig>, mut pending:ResMut<PendingCommands>, mut writer:ConsoleWriter) {
+    // Skip lines that repeat themselves, e.g. `echo hi; !!` or an alias of `!!`, which would otherwise be queued
+    // again every frame.
+    match history.entries().iter().rev().find(|line| !repeats(line, &aliases, config.case_insensitive, 0)) {
+        Some(line) => {
+            writer.writeln(format!("Repeating: {line}"));
+            pending.push_back(line.clone());
+        },
+        None => writer.writeln("No previous command."),
+    }
+}
+
+fn echo(args:Res<CommandArgs>, mut writer:ConsoleWriter) {
+    writer.writeln(args.args().join(" "));
+}
+
+/// Prints the crate version, followed by [`ConsoleConfig::game_version`] if set.
+fn version(config:Res<ConsoleConfig>, mut writer:ConsoleWriter) {
+    let crate_version = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+    match &config.game_version {
+        Some(game) => writer.writeln(format!("{crate_version} / {game}")),
+        None => writer.writeln(crate_version),
+    }
 }
 
-#[derive(States, Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
-enum CmdTrigger{
-    #[default]
-    Ready,
-    Fired
+/// Copies the selected output lines, or the whole output if nothing is selected.
+fn copy(mut writer:ConsoleWriter) {
+    let text = writer.output.selected_text().unwrap_or_else(|| writer.output.lines().collect::<Vec<_>>().join("\n"));
+    let lines = text.lines().count();
+    if clipboard_set(text) {
+        writer.writeln(format!("Copied {lines} lines."));
+    } else {
+        writer.writeln_colored("Couldn't access the clipboard.", Color::RED);
+    }
 }
 
-fn move_console(time:Res<Time<Real>>, mut style:Query<&mut Style, With<ConsoleTag>>, state:Res<State<ConsoleState>>, mut commands:Commands, mut evts:ResMut<Events<ReceivedCharacter>>) {
-    const MOVE_SPEED:f32 = 100.0;
-    let top = &mut style.single_mut().top;
-    let mut pos = if let Val::Percent(pos) = top { *pos } else { -33.3 };
-    pos += match **state {
-        ConsoleState::AnimatingOpen => MOVE_SPEED*time.delta_seconds(),
-        ConsoleState::AnimatingClosed => -MOVE_SPEED*time.delta_seconds(),
-        _ => 0.0
+fn log_to_file(args:Res<CommandArgs>, mut writer:ConsoleWriter) {
+    let [path] = args.args()[..] else {
+        writer.writeln_colored("Usage: log_to_file <file>", Color::YELLOW);
+        return;
     };
-    *top = Val::Percent(pos);
+    match writer.file_log.start(path) {
+        Ok(()) => writer.writeln(format!("Logging console output to {path}")),
+        Err(err) => writer.writeln_colored(format!("Couldn't open {path}: {err}"), Color::RED),
+    }
+}
 
-    match **state {
-        ConsoleState::AnimatingClosed => if pos <= -33.3 { 
-            *top = Val::Percent(-33.3); 
-            commands.insert_resource(NextState(Some(ConsoleState::Closed))) 
+fn stop_logging(mut writer:ConsoleWriter) {
+    match writer.file_log.stop() {
+        Some(path) => writer.writeln(format!("Stopped logging to {}", path.display())),
+        None => writer.writeln("Not logging to a file."),
+    }
+}
+
+/// Smallest and largest size `fontsize` accepts.
+const FONT_SIZE_RANGE:std::ops::RangeInclusive<f32> = 6.0..=96.0;
+
+/// Prints or changes [`ConsoleTheme::font_size`], clamped to [`FONT_SIZE_RANGE`].
+fn font_size(args:Res<CommandArgs>, mut theme:ResMut<ConsoleTheme>, mut writer:ConsoleWriter) {
+    let [size] = args.args()[..] else {
+        writer.writeln(format!("Font size is {}.", theme.font_size));
+        return;
+    };
+    match size.parse::<f32>() {
+        Ok(size) if size.is_finite() => {
+            theme.font_size = size.clamp(*FONT_SIZE_RANGE.start(), *FONT_SIZE_RANGE.end());
+            writer.writeln(format!("Font size set to {}.", theme.font_size));
         },
-        ConsoleState::AnimatingOpen => if pos >= 0.0 { 
-            *top = Val::Percent(0.); 
-            commands.insert_resource(NextState(Some(ConsoleState::Open)));
-            evts.clear();
+        _ => writer.writeln_colored(format!("{size} is not a number."), Color::RED),
+    }
+}
+
+/// Prints the output lines containing the pattern, with their line numbers.
+fn grep(args:Res<CommandArgs>, mut writer:ConsoleWriter) {
+    let pattern = args.args().join(" ");
+    let matches:Vec<_> = writer.output.numbered_lines()
+        // Skip the line being written to and, with echo on, this very command.
+        .filter(|(_, line)| !line.is_empty() && *line != args.raw())
+        .filter(|(_, line)| line.contains(&pattern))
+        .map(|(number, line)| format!("{number}: {line}"))
+        .collect();
+    if matches.is_empty() {
+        writer.writeln(format!("No lines contain {pattern:?}."));
+    }
+    for line in matches {
+        writer.writeln(line);
+    }
+}
+
+/// Prints the current and average frame rate measured by [`FrameTimeDiagnosticsPlugin`].
+fn fps(store:Option<Res<DiagnosticsStore>>, mut writer:ConsoleWriter) {
+    let Some(fps) = store.as_deref().and_then(|store| store.get(&FrameTimeDiagnosticsPlugin::FPS)) else {
+        writer.writeln_colored("FPS isn't measured, add FrameTimeDiagnosticsPlugin to the app.", Color::YELLOW);
+        return;
+    };
+    match (fps.value(), fps.average()) {
+        (Some(current), Some(average)) => writer.writeln(format!("{current:.1} fps, {average:.1} on average")),
+        _ => writer.writeln("No frames measured yet."),
+    }
+}
+
+fn diagnostic_value(diagnostic:&Diagnostic) -> String {
+    let value = |value:Option<f64>| value.map_or_else(|| "-".to_owned(), |value| format!("{value:.2}{}", diagnostic.suffix));
+    format!("{} (average {})", value(diagnostic.value()), value(diagnostic.average()))
+}
+
+/// Prints one or all diagnostics registered with Bevy's [`DiagnosticsStore`].
+fn diag(args:Res<CommandArgs>, store:Option<Res<DiagnosticsStore>>, mut writer:ConsoleWriter) {
+    let Some(store) = store else {
+        writer.writeln_colored("No diagnostics available, add Bevy's DiagnosticsPlugin to the app.", Color::YELLOW);
+        return;
+    };
+    if let [name] = args.args()[..] {
+        match store.iter().find(|diagnostic| diagnostic.path().as_str() == name) {
+            Some(diagnostic) => writer.writeln(format!("{name}: {}", diagnostic_value(diagnostic))),
+            None => writer.writeln_colored(format!("No diagnostic named {name}."), Color::RED),
+        }
+        return;
+    }
+    let mut rows:Vec<_> = store.iter().map(|diagnostic| vec![diagnostic.path().to_string(), diagnostic_value(diagnostic)]).collect();
+    if rows.is_empty() {
+        writer.writeln("No diagnostics registered.");
+        return;
+    }
+    rows.sort_unstable();
+    writer.write_table(rows);
+}
+
+fn clear(mut writer:ConsoleWriter) {
+    writer.clear();
+}
+
+/// Splits the arguments of `bind`-like commands into the first one and the command line after it.
+/// The command is taken as typed so its own quoting survives, `bind F5 say "a b"` binds `say "a b"`.
+/// A command given as one quoted argument is unquoted, as `writeconfig` writes them.
+fn split_command_arg(args:&CommandArgs) -> Option<(String, String)> {
+    let first = args.args().first()?.to_string();
+    let rest = split_name(split_name(args.raw()).1).1.trim();
+    let cmd = match &tokenize(rest)[..] {
+        [] => return None,
+        [single] => single.clone(),
+        _ => rest.to_owned(),
+    };
+    Some((first, cmd))
+}
+
+fn bind(args:Res<CommandArgs>, config:Res<ConsoleConfig>, mut bindings:ResMut<KeyBindings>, mut writer:ConsoleWriter) {
+    let (key_name, cmd) = match split_command_arg(&args) {
+        Some(split) => split,
+        None => {
+            writer.writeln_colored("Usage: bind <key> <command...>", Color::YELLOW);
+            return;
         },
-        _ => ()
-    }  
-}
-
-fn setup_ui(mut commands:Commands) {
-    commands.spawn((ConsoleTag, NodeBundle{style:Style{
-            position_type:PositionType::Absolute,
-            display:Display::Flex,
-            flex_direction:FlexDirection::Column,
-            padding:UiRect::px(12., 12., 12., 0.0),
-            width:Val::Percent(100.),
-            top:Val::Percent(-33.3),
-            height:Val::Percent(33.3),
-            min_height:Val::Percent(33.3),
-            max_height:Val::Percent(33.3),
-            ..default()
+    };
+    match parse_keycode(&key_name) {
+        Some(key) if key == config.toggle_key => writer.writeln_colored("Can't bind the console toggle key.", Color::RED),
+        Some(key) => {
+            bindings.insert(key, cmd);
         },
-        background_color:BackgroundColor(Color::BLACK),
-        ..default()
-    })).with_children(|console|{
-        console.spawn((ConsoleOutputTag, TextBundle{style:Style{width:Val::Percent(100.), height:Val::Percent(80.), min_height:Val::Percent(80.), ..default()}, text:Text::from_section("" , TextStyle::default()), ..default()}));
-        console.spawn((ConsoleInputTag, TextBundle{style:Style{width:Val::Percent(100.), ..default()}, text:Text::from_sections(vec![TextSection{value:" > ".into(), ..default()}, TextSection::default(), TextSection{value:"|".into(), style:TextStyle::default()}]), ..default()}));
-    });
+        None => writer.writeln_colored(unknown_key(&key_name), Color::RED),
+    }
 }
 
-/// A resource modified before the system call to include command line arguments meant for that system.
-#[derive(Resource, Deref, DerefMut)]
-pub struct CommandArgs(String);
+fn unbind(args:Res<CommandArgs>, mut bindings:ResMut<KeyBindings>, mut writer:ConsoleWriter) {
+    let [key_name] = args.args()[..] else {
+        writer.writeln_colored("Usage: unbind <key>", Color::YELLOW);
+        return;
+    };
+    match parse_keycode(key_name) {
+        Some(key) => if bindings.remove(&key).is_none() {
+            writer.writeln(format!("{key_name} is not bound."));
+        },
+        None => writer.writeln_colored(unknown_key(key_name), Color::RED),
+    }
+}
 
+fn binds(bindings:Res<KeyBindings>, mut writer:ConsoleWriter) {
+    if bindings.is_empty() {
+        writer.writeln("No binds set.");
+        return;
+    }
+    let mut binds:Vec<_> = bindings.iter().map(|(key, cmd)| (keycode_name(*key), cmd)).collect();
+    binds.sort_unstable();
+    writer.write_table(binds.into_iter().map(|(key, cmd)| vec![key.to_owned(), "->".to_owned(), cmd.clone()]).collect());
+}
 
-fn text_input(
-    mut evr_char: ResMut<Events<ReceivedCharacter>>,
-    kbd: Res<ButtonInput<KeyCode>>,
-    map:Res<CommandMap>,
-    mut input_field:Query<&mut Text, (With<ConsoleInputTag>, Without<ConsoleOutputTag>)>,
-    mut output_field:Query<&mut Text, (With<ConsoleOutputTag>, Without<ConsoleInputTag>)>,
-    mut commands:Commands,
-) {
-    if kbd.just_pressed(KeyCode::Enter) {
-        let cmd = std::mem::take(&mut input_field.single_mut().sections[1].value);
-        let out = &mut output_field.single_mut().sections[0].value;
-        out.push_str(cmd.as_str());
-        out.push('\n');
-        commands.insert_resource(CommandArgs(cmd));
-        // Running cmd system in the next frame to make sure CommandArgs resource has been properly set. 
-        commands.insert_resource(NextState(Some(CmdTrigger::Fired)))
-    } else if kbd.just_pressed(KeyCode::Backspace) {
-        input_field.single_mut().sections[1].value.pop();
-    } else if kbd.just_pressed(KeyCode::Tab) {
-        let input = input_field.single();
-        let cmd_start = input.sections[1].value.as_str();
-        let out = &mut output_field.single_mut().sections[0].value;
-        let mut is_found_one = false;
-        for cmd in (**map).keys() {
-            if cmd.starts_with(cmd_start) {
-                out.push_str(cmd);
-                out.push(' ');
-                is_found_one = true;
-            }
-        }
-        if is_found_one {
-            out.push('\n')
-        } else {
-            out.push_str("No commands start with that.\n");
-        }
-    } else if !kbd.just_pressed(KeyCode::Backquote) {
-        for ev in evr_char.drain() {
-            // ignore control (special) characters
-            for char in ev.char.chars() {
-                if !char.is_control() {
-                    input_field.single_mut().sections[1].value.push(char);
-                }
-            }
-        }
+fn alias(args:Res<CommandArgs>, mut aliases:ResMut<CommandAliases>, mut writer:ConsoleWriter) {
+    let (name, target) = match split_command_arg(&args) {
+        Some(split) => split,
+        None => {
+            writer.writeln_colored("Usage: alias <name> <command...>", Color::YELLOW);
+            return;
+        },
+    };
+    aliases.insert(name.into(), target.into());
+}
+
+/// Writes the binds, aliases and cvars as commands that `exec` runs to restore them.
+fn write_config(args:Res<CommandArgs>, bindings:Res<KeyBindings>, aliases:Res<CommandAliases>, cvars:Res<Cvars>, mut writer:ConsoleWriter) {
+    let [path] = args.args()[..] else {
+        writer.writeln_colored("Usage: writeconfig <file>", Color::YELLOW);
+        return;
+    };
+    let mut contents = String::from("// Written by writeconfig, run it with exec\n");
+    let mut binds:Vec<_> = bindings.iter().map(|(key, cmd)| (keycode_name(*key), cmd)).collect();
+    binds.sort_unstable();
+    for (key, cmd) in binds {
+        let _ = writeln!(contents, "bind {key} {}", quote(cmd));
+    }
+    let mut aliases:Vec<_> = aliases.iter().collect();
+    aliases.sort_unstable();
+    for (name, target) in aliases {
+        let _ = writeln!(contents, "alias {name} {}", quote(target));
+    }
+    write_cvars(&mut contents, &cvars);
+    match std::fs::write(path, contents) {
+        Ok(()) => writer.writeln(format!("Wrote config to {path}")),
+        Err(err) => writer.writeln_colored(format!("Couldn't write {path}: {err}"), Color::RED),
     }
 }
 
-fn run_cmd(cmd:Res<CommandArgs>, map: Res<CommandMap>, mut output_field:Query<&mut Text, (With<ConsoleOutputTag>, Without<ConsoleInputTag>)>, mut commands:Commands) {
-    if let Some(call) = cmd.split(' ').next() {
-        if let Some(id) = (**map).get(call) {
-            commands.run_system(*id)
-        } else {
-            let console = &mut output_field.single_mut().sections[0].value;
-            console.push_str("Command not found: ");
-            console.push_str(call);
-            console.push('\n')
+/// Looks up a type in the type registry by its short or full type path.
+fn find_type<'a>(registry:&'a TypeRegistry, name:&str) -> Result<&'a TypeRegistration, String> {
+    registry.get_with_short_type_path(name).or_else(|| registry.get_with_type_path(name))
+        .ok_or_else(|| format!("Unknown type {name}. Register it with App::register_type, or use its full path if the name is ambiguous."))
+}
+
+/// Prints how many entities have the named component, looked up like in `spawn_component`.
+fn count(args:Res<CommandArgs>, registry:Res<AppTypeRegistry>, components:&Components, archetypes:&Archetypes, mut writer:ConsoleWriter) -> Result<(), String> {
+    let [name] = args.args()[..] else { return Ok(()) };
+    let type_id = find_type(&registry.read(), name)?.type_id();
+    // Types that were never added to an entity have no id yet.
+    let count:usize = components.get_id(type_id).map_or(0, |id| {
+        archetypes.iter().filter(|archetype| archetype.contains(id)).map(|archetype| archetype.len()).sum()
+    });
+    let entities = if count == 1 { "entity" } else { "entities" };
+    writer.writeln(format!("{count} {entities} with {name}"));
+    Ok(())
+}
+
+/// Marks entities spawned with the `spawn_component` command, so `despawn` can clean them up.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct SpawnedByConsole;
+
+/// Spawns an entity with the default value of every named component. Types are looked up in the [`AppTypeRegistry`]
+/// by their short or full type path and need `#[reflect(Component, Default)]`.
+fn spawn_component(args:Res<CommandArgs>, registry:Res<AppTypeRegistry>, mut commands:Commands, mut writer:ConsoleWriter) -> Result<(), String> {
+    let mut components = Vec::new();
+    {
+        let registry = registry.read();
+        for name in args.args() {
+            let registration = find_type(&registry, name)?;
+            let component = registration.data::<ReflectComponent>()
+                .ok_or_else(|| format!("{name} isn't a component, it needs #[reflect(Component)]"))?;
+            let default = registration.data::<ReflectDefault>()
+                .ok_or_else(|| format!("{name} can't be created, it needs #[reflect(Default)]"))?;
+            components.push((component.clone(), default.default()));
         }
     }
+    let entity = commands.spawn(SpawnedByConsole).id();
+    let registry = registry.clone();
+    commands.add(move |world:&mut World| {
+        let registry = registry.read();
+        let mut entity = world.entity_mut(entity);
+        for (component, value) in components {
+            component.insert(&mut entity, value.as_ref(), &registry);
+        }
+    });
+    writer.writeln(format!("Spawned {entity:?}"));
+    Ok(())
 }
 
+/// Despawns the named entities spawned with `spawn_component`, or every one of them if none are named. Entities are
+/// named as printed when spawned, e.g. `3v1`, or by their index alone.
+fn despawn(args:Res<CommandArgs>, spawned:Query<Entity, With<SpawnedByConsole>>, mut commands:Commands, mut writer:ConsoleWriter) -> Result<(), String> {
+    let names = args.args();
+    let targets:Vec<Entity> = if names.is_empty() {
+        spawned.iter().collect()
+    } else {
+        names.iter().map(|name| {
+            spawned.iter().find(|entity| format!("{entity:?}") == *name || entity.index().to_string() == *name)
+                .ok_or_else(|| format!("{name} isn't an entity spawned by the console"))
+        }).collect::<Result<_, _>>()?
+    };
+    for entity in &targets {
+        commands.entity(*entity).despawn_recursive();
+    }
+    let entities = if targets.len() == 1 { "entity" } else { "entities" };
+    writer.writeln(format!("Despawned {} {entities}", targets.len()));
+    Ok(())
+}
 
-#[derive(States, Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
-struct CommandLineCommandsTrigger(u16);
+/// Queues every line of a script file, skipping blank lines and `//` comments.
+fn exec(args:Res<CommandArgs>, mut pending:ResMut<PendingCommands>, mut writer:ConsoleWriter) {
+    let [path] = args.args()[..] else {
+        writer.writeln_colored("Usage: exec <file>", Color::YELLOW);
+        return;
+    };
+    match std::fs::read_to_string(path) {
+        Ok(script) => pending.extend(script.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(str::to_owned)),
+        Err(err) => writer.writeln_colored(format!("Couldn't read {path}: {err}"), Color::RED),
+    }
+}
 
-#[derive(Resource, Default, Deref, DerefMut)]
-pub struct CommandMap(HashMap<std::borrow::Cow<'static, str>, SystemId>);
+fn set_cvar(args:Res<CommandArgs>, mut cvars:ResMut<Cvars>, mut writer:ConsoleWriter) {
+    let [name, text] = args.args()[..] else {
+        writer.writeln_colored("Usage: set <name> <value>", Color::YELLOW);
+        return;
+    };
+    let result = match cvars.get_value(name) {
+        Some(current) => current.parse_same(text),
+        None => Err(format!("Unknown cvar: {name}")),
+    };
+    match result.and_then(|value| cvars.set(name, value)) {
+        Ok(()) => writer.writeln(format!("{name} = {}", cvars.values[name])),
+        Err(err) => writer.writeln_colored(err, Color::RED),
+    }
+}
 
+fn get_cvar(args:Res<CommandArgs>, cvars:Res<Cvars>, mut writer:ConsoleWriter) {
+    match args.args()[..] {
+        [] => {
+            let mut all:Vec<_> = cvars.iter().collect();
+            all.sort_unstable_by_key(|(name, _)| *name);
+            for (name, value) in all {
+                writer.writeln(format!("{name} = {value}"));
+            }
+        },
+        [name] => match cvars.get_value(name) {
+            Some(value) => writer.writeln(format!("{name} = {value}")),
+            None => writer.writeln_colored(format!("Unknown cvar: {name}"), Color::RED),
+        },
+        _ => writer.writeln_colored("Usage: get [name]", Color::YELLOW),
+    }
+}
 
-/// Creates a drop down console that can be used to call one-shot systems
-/// To add system as a callable command - use 
-/// ```
-/// # let app = App:new();
-/// # fn your_system() {}
-/// ConsolePlugin::add_cmd(&mut app, "run_me", your_system);
-/// ```
-/// The command arguments will be available to your system through [`CommandArgs`] resource.
-/// Warning - the key "run_me" as well as [`SystemId`] generated from your system will be put in a public resource
-/// so other plugins can mess with its keys and values, resuling in potentially unexpected system calls.
-/// Inside the called system you can get the console output with
-/// ```
-/// mut out:Query<&mut Text, With<ConsoleOutputTag>>
-/// ```
-pub struct ConsolePlugin;
-impl ConsolePlugin{
-    pub fn add_cmd<M, S>(app:&mut App, name:impl Into<std::borrow::Cow<'static, str>>, system: S ) -> Option<SystemId>
-where
-    S: IntoSystem<(), (), M> + 'static,
-    {
-        let test = app.world.register_system(system);
-        app.world.init_resource::<CommandMap>(); // Calling this just in case someone adds systems before registering the plugin.
-        app.world.resource_mut::<CommandMap>().as_deref_mut().insert(name.into(), test)
+const SCROLLBACK_CVAR:&str = "scrollback";
+
+fn validate_scrollback(value:&CvarValue) -> Result<(), String> {
+    match value {
+        CvarValue::I32(lines) if *lines > 0 => Ok(()),
+        _ => Err(format!("{SCROLLBACK_CVAR} must be a positive number of lines")),
     }
 }
 
-fn help(map:Res<CommandMap>, mut out:Query<&mut Text, With<ConsoleOutputTag>>) {
-    let out = &mut out.single_mut().sections[0].value;
-    out.push_str("Registered commands:\n");
-    for cmd in (**map).keys() {
-        out.push_str(cmd);
-        out.push(' ');
+/// Resizes [`ConsoleOutput`] when the `scrollback` cvar changes. [`validate_scrollback`] keeps it positive.
+fn apply_scrollback(cvars:Res<Cvars>, mut output:ResMut<ConsoleOutput>) {
+    let Some(lines) = cvars.get::<i32>(SCROLLBACK_CVAR).and_then(|lines| usize::try_from(lines).ok()) else { return };
+    if lines != output.max_lines() {
+        output.set_max_lines(lines);
     }
-    out.push('\n')
 }
 
 impl Plugin for ConsolePlugin {
     fn build(&self, app: &mut App) {
+        let id = self.id.clone().unwrap_or(Cow::Borrowed(DEFAULT_CONSOLE));
+        if let Some(instances) = app.world.get_resource::<ConsoleInstances>() {
+            assert!(!instances.contains(&id), "Console {id} is already added");
+            self.add_parked(app, id);
+            return;
+        }
+        if let Some(config) = &self.config {
+            app.insert_resource(config.clone());
+        }
+        let run_cmd = app.world.register_system(run_cmd);
+        app.insert_resource(RunCmd(run_cmd));
+        if let Some(theme) = &self.theme {
+            app.insert_resource(theme.clone());
+        }
         app
             .init_state::<ConsoleState>()
             .init_state::<CommandLineCommandsTrigger>()
-            .init_state::<CmdTrigger>()
             .init_resource::<CommandMap>()
+            .init_resource::<CommandAliases>()
+            .init_resource::<CommandFilter>()
+            .init_resource::<Cvars>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<PendingCommands>()
+            .init_resource::<LastCommandResult>()
+            .init_resource::<CommandError>()
+            .init_resource::<ConsoleConfig>()
+            .init_resource::<ConsoleTheme>()
+            .init_resource::<CommandHistory>()
+            .init_resource::<ConsoleOutput>()
+            .init_resource::<ConsoleFileLog>()
+            .init_resource::<InputLine>()
+            .init_resource::<Suggestions>()
+            .init_resource::<PausedByConsole>()
+            .init_resource::<CurrentInput>()
+            .add_event::<InputChanged>()
+            .add_event::<CommandSubmitted>()
+            .add_event::<ConsoleKeyTyped>()
+            .add_event::<ConsoleCommandEntered>()
+            .add_event::<CommandExecuted>()
+            .add_event::<CommandNotFound>()
+            .add_event::<ConsolePrinted>()
+            .add_systems(Startup, load_history.run_if(persist_history_enabled))
+            .add_systems(Last, save_history.run_if(persist_history_enabled))
+            .add_systems(PreStartup, load_cvars.run_if(persist_cvars_enabled))
+            .add_systems(Last, save_cvars.run_if(persist_cvars_enabled))
+            .add_systems(Last, flush_file_log)
+            .add_systems(First, tick_output_clock)
+            .add_systems(Update, print_captured_logs.before(render_output))
+            .add_systems(Update, apply_scrollback.run_if(resource_changed::<Cvars>).before(render_output))
+            .add_systems(Last, save_parked_histories.run_if(on_event::<AppExit>()))
+            ;
+        name_history_file(&mut app.world.resource_mut::<ConsoleConfig>(), &id);
+        app.insert_resource(ConsoleInstances { active:id, parked:HashMap::new() });
+        if self.headless {
+            app.add_systems(Update, (submit_command, run_pending).chain());
+        } else {
+            Self::add_ui(app);
+        }
+        Self::register_builtin_cvars(app);
+        Self::register_builtin_cmds(app);
+    }
+
+    /// Consoles after the first are added to it.
+    fn is_unique(&self) -> bool {
+        false
+    }
+
+    /// Checks for the plugins the UI relies on once every plugin is added, so their order doesn't matter.
+    fn finish(&self, app:&mut App) {
+        // Only the first console decides whether there is a UI.
+        if !app.world.contains_resource::<ConsoleAnimation>() {
+            return;
+        }
+        let world = &app.world;
+        let missing:Vec<&str> = [
+            ("InputPlugin", world.contains_resource::<ButtonInput<KeyCode>>() && world.contains_resource::<ButtonInput<MouseButton>>()
+                && world.contains_resource::<Events<MouseWheel>>()),
+            ("WindowPlugin", world.contains_resource::<Events<ReceivedCharacter>>() && world.contains_resource::<Events<Ime>>()),
+            ("TimePlugin", world.contains_resource::<Time<Real>>()),
+        ].into_iter().filter(|(_, found)| !found).map(|(plugin, _)| plugin).collect();
+        if !missing.is_empty() {
+            panic!("ConsolePlugin needs {} - add DefaultPlugins, or use ConsolePlugin::headless() for a console without UI", missing.join(", "));
+        }
+    }
+}
+
+impl ConsolePlugin {
+    /// The UI and the systems reading the keyboard, left out by [`ConsolePlugin::headless`].
+    fn add_ui(app:&mut App) {
+        app
+            .init_resource::<ConsoleAnimation>()
+            .init_resource::<ArgCandidates>()
             .add_systems(Startup, setup_ui)
-            .add_systems(Update, next_state(ConsoleState::AnimatingOpen).run_if(input_just_pressed(KeyCode::Backquote).and_then(in_state(ConsoleState::Closed))))
-            .add_systems(Update, next_state(ConsoleState::AnimatingClosed).run_if(input_just_pressed(KeyCode::Backquote).and_then(in_state(ConsoleState::Open))))
-            .add_systems(Update, move_console.run_if(in_state(ConsoleState::AnimatingClosed).or_else(in_state(ConsoleState::AnimatingOpen))))
-            .add_systems(Update, text_input.run_if(in_state(ConsoleState::Open)))
-            .add_systems(OnEnter(CmdTrigger::Fired), (next_state(CmdTrigger::Ready), run_cmd))
+            .add_systems(Update, switch_console.run_if(has_parked_consoles).in_set(ConsoleSet).before(toggle_console))
+            .add_systems(Update, (toggle_console, consume_toggle_key).chain().run_if(toggle_key_pressed).in_set(ConsoleSet).before(text_input))
+            .add_systems(Update, (animate_console.run_if(console_is_animating()), apply_animation.run_if(resource_changed::<ConsoleAnimation>)).chain())
+            .add_systems(Update, (ime_input, gather_arg_candidates.run_if(tab_pressed), text_input).chain().run_if(in_state(ConsoleState::Open)).in_set(ConsoleSet))
+            .add_systems(OnEnter(ConsoleState::AnimatingOpen), pause_time)
+            .add_systems(OnEnter(ConsoleState::Closed), restore_time)
+            .add_systems(OnEnter(ConsoleState::Open), enable_ime)
+            .add_systems(OnExit(ConsoleState::Open), disable_ime)
+            .add_systems(Update, run_binds.run_if(not(in_state(ConsoleState::Open))))
+            .add_systems(Update, submit_command.after(text_input).in_set(ConsoleSet))
+            .add_systems(Update, run_pending.after(ConsoleSet).after(run_binds))
+            .add_systems(Update, capture_keyboard.run_if(capture_input_enabled.and_then(in_state(ConsoleState::Open))).after(ConsoleSet))
+            .add_systems(OnEnter(ConsoleState::Open), release_keyboard.run_if(capture_input_enabled))
+            .add_systems(Update, select_output.run_if(in_state(ConsoleState::Open)).before(render_output))
+            .add_systems(Update, scroll_output.run_if(in_state(ConsoleState::Open)).before(render_output))
+            .add_systems(Update, apply_theme.run_if(resource_changed::<ConsoleTheme>).before(render_output).before(render_input))
+            .add_systems(Update, apply_prompt.run_if(resource_changed::<ConsoleConfig>).before(render_input))
+            .add_systems(Update, (render_input, render_output).after(text_input))
+            .add_systems(Update, publish_input.run_if(resource_changed::<InputLine>).after(ConsoleSet))
+            .add_systems(Update, (update_suggestions.run_if(resource_changed::<InputLine>), render_suggestions.run_if(resource_changed::<Suggestions>)).chain().after(text_input))
+            .add_systems(Update, blink_cursor.after(render_input).run_if(in_state(ConsoleState::Open)))
             ;
-        Self::add_cmd(app, "help", help);
+    }
+
+    /// Cvars are shared by every console, so these are registered with the first one.
+    fn register_builtin_cvars(app:&mut App) {
+        let scrollback = i32::try_from(app.world.resource::<ConsoleOutput>().max_lines()).unwrap_or(i32::MAX);
+        Self::register_cvar(app, SCROLLBACK_CVAR, scrollback);
+        Self::set_cvar_validator(app, SCROLLBACK_CVAR, validate_scrollback);
+        Self::register_cvar(app, CHEATS_CVAR, PermissionLevel::User.value());
+    }
+
+    fn register_builtin_cmds(app:&mut App) {
+        Self::add_cmd_with_usage(app, "help", "Lists registered commands, or describes one command, alias or category", "help [command|alias|category]", help);
+        Self::add_cmd_with_help(app, "clear", "Clears the console output", clear);
+        Self::add_cmd_with_usage(app, "grep", "Prints the output lines containing a text, with their line numbers", "grep <text...>", grep);
+        Self::add_cmd_with_usage(app, "log_to_file", "Appends all console output to a file from now on", "log_to_file <file>", log_to_file);
+        Self::add_cmd_with_help(app, "stop_logging", "Stops log_to_file", stop_logging);
+        Self::add_cmd_with_help(app, "copy", "Copies the selected output lines, or all of them, to the clipboard", copy);
+        Self::add_cmd_with_help(app, "version", "Prints the console and game versions", version);
+        Self::add_cmd_with_help(app, "fps", "Prints the frame rate", fps);
+        Self::insert_cmd(app, "diag".into(), |world| {
+            let id = world.register_system(diag);
+            let completer = ArgCompleter::with_world(|_, world| world.get_resource::<DiagnosticsStore>()
+                .map(|store| store.iter().map(|diagnostic| diagnostic.path().to_string()).collect())
+                .unwrap_or_default());
+            CommandInfo { description:Some("Prints one or all of Bevy's diagnostics".into()), usage:Some("diag [name]".into()),
+                completer:Some(completer), ..CommandInfo::new(id) }
+        });
+        Self::add_cmd_with_usage(app, "fontsize", "Prints or changes the console font size", "fontsize [size]", font_size);
+        Self::add_cmd_with_help(app, "last_result", "Prints whether the previous command succeeded", last_result);
+        Self::add_cmd_with_help(app, REPEAT_CMD, "Runs the previous command again", repeat);
+        Self::add_cmd_with_usage(app, "echo", "Prints its arguments", "echo [text...]", echo);
+        Self::add_cmd_with_usage(app, "bind", "Runs a command whenever a key is pressed", "bind <key> <command...>", bind);
+        Self::add_cmd_with_help(app, "binds", "Lists key bindings", binds);
+        Self::add_cmd_with_usage(app, "unbind", "Removes a key binding", "unbind <key>", unbind);
+        Self::add_cmd_with_usage(app, "alias", "Makes a name run a command", "alias <name> <command...>", alias);
+        Self::add_cmd_with_usage(app, "writeconfig", "Saves binds, aliases and cvars to a file that exec restores", "writeconfig <file>", write_config);
+        Self::insert_cmd(app, "spawn_component".into(), |world| {
+            let id = world.register_system(spawn_component.pipe(report_cmd_error));
+            CommandInfo { description:Some("Spawns an entity with reflected components set to their defaults".into()),
+                usage:Some("spawn_component <type...>".into()), permission:PermissionLevel::Cheat, ..CommandInfo::new(id) }
+        });
+        Self::insert_cmd(app, "despawn".into(), |world| {
+            let id = world.register_system(despawn.pipe(report_cmd_error));
+            CommandInfo { description:Some("Despawns entities spawned by spawn_component, or all of them".into()),
+                usage:Some("despawn [entities...]".into()), permission:PermissionLevel::Cheat, ..CommandInfo::new(id) }
+        });
+        Self::insert_cmd(app, "count".into(), |world| {
+            let id = world.register_system(count.pipe(report_cmd_error));
+            CommandInfo { description:Some("Counts the entities with a reflected component".into()),
+                usage:Some("count <type>".into()), ..CommandInfo::new(id) }
+        });
+        Self::add_cmd_with_usage(app, "exec", "Runs every line of a file as a command", "exec <file>", exec);
+        Self::add_cmd_with_usage(app, "set", "Changes a console variable", "set <name> <value>", set_cvar);
+        Self::add_cmd_with_usage(app, "get", "Prints one or all console variables", "get [name]", get_cvar);
         // let mut map = HashMap::new();
         // for (idx, (call, system)) in self.callstate_map.iter().zip(self.systems.iter()).enumerate() {
         //     // app.add_systems(OnEnter(CommandLineCommandsTrigger((idx+1) as u16)), system);
@@ -206,4 +4155,552 @@ impl Plugin for ConsolePlugin {
         // }
         // app.insert_resource(CommandMap(map));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::{keyboard::{Key, KeyboardInput}, ButtonState};
+
+    fn headless_app(plugin:ConsolePlugin) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, plugin));
+        app
+    }
+
+    #[test]
+    fn parse_args_into_tuple() {
+        let args = CommandArgs::new("teleport 1.0 2.5 foo".into());
+        assert_eq!(args.parse_args::<(f32, f32, String)>(), Ok((1.0, 2.5, "foo".to_owned())));
+    }
+
+    #[test]
+    fn parse_args_reports_bad_argument() {
+        let args = CommandArgs::new("teleport x".into());
+        assert!(matches!(args.parse_args::<(f32, f32)>(), Err(ArgError::Invalid { index:0, .. })));
+        let args = CommandArgs::new("teleport 1".into());
+        assert_eq!(args.parse_args::<(f32, f32)>(), Err(ArgError::Missing { index:1 }));
+    }
+
+    #[test]
+    fn find_name_ignores_case_when_asked() {
+        let map = HashMap::from([(Cow::Borrowed("help"), ())]);
+        for typed in ["HELP", "Help", "help"] {
+            assert_eq!(find_name(&map, typed, true).map(|(name, _)| name.as_ref()), Some("help"));
+        }
+        assert!(find_name(&map, "HELP", false).is_none());
+    }
+
+    #[test]
+    fn mixed_case_runs_the_registered_command() {
+        let mut app = headless_app(ConsolePlugin::headless().case_insensitive(true));
+        ConsolePlugin::add_cmd(&mut app, "whoami", |args:Res<CommandArgs>, mut writer:ConsoleWriter| writer.writeln(args.name()));
+        assert_eq!(ConsolePlugin::execute(&mut app, "WHOAMI"), "whoami\n");
+        assert_eq!(ConsolePlugin::execute(&mut app, "WhoAmI"), "whoami\n");
+        assert!(!ConsolePlugin::execute(&mut app, "HELP").starts_with("Command not found"));
+        assert!(!ConsolePlugin::execute(&mut app, "Help").starts_with("Command not found"));
+    }
+
+    #[test]
+    fn completion_lists_canonical_names() {
+        let mut app = headless_app(ConsolePlugin::headless().case_insensitive(true));
+        app.world.resource_mut::<InputLine>().set("HE".into(), None);
+        app.world.run_system_once(update_suggestions);
+        assert_eq!(app.world.resource::<Suggestions>().names, ["help"]);
+    }
+
+    fn queue_two(mut pending:ResMut<PendingCommands>) {
+        pending.push_back("echo second".into());
+        pending.push_back("echo third".into());
+    }
+
+    #[test]
+    fn queued_commands_all_run() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        ConsolePlugin::add_cmd(&mut app, "first", queue_two);
+        assert_eq!(ConsolePlugin::execute(&mut app, "first"), "second\nthird\n");
+
+        let mut app = headless_app(ConsolePlugin::headless().echo_input(false));
+        ConsolePlugin::add_cmd(&mut app, "first", queue_two);
+        app.world.send_event(CommandSubmitted { line:"first".into() });
+        for _ in 0..3 {
+            app.update();
+        }
+        let lines:Vec<_> = app.world.resource::<ConsoleOutput>().lines().collect();
+        assert_eq!(lines, ["second", "third", ""]);
+    }
+
+    fn typed(text:&str) -> InputLine {
+        let mut input = InputLine::default();
+        for char in text.chars() {
+            assert!(input.insert(char, None));
+        }
+        input
+    }
+
+    fn assert_on_boundary(input:&InputLine) {
+        assert!(input.text.is_char_boundary(input.cursor), "cursor {} splits {:?}", input.cursor, input.text);
+    }
+
+    #[test]
+    fn input_line_edits_whole_chars() {
+        let mut input = typed("né😀");
+        assert_eq!((input.cursor, input.cursor_chars()), ("né😀".len(), 3));
+        input.backspace();
+        assert_eq!(input.text, "né");
+        input.left();
+        assert_on_boundary(&input);
+        assert_eq!(input.cursor_chars(), 1);
+        input.insert('🦀', None);
+        assert_eq!(input.text, "n🦀é");
+        input.delete();
+        assert_eq!(input.text, "n🦀");
+        input.left();
+        input.left();
+        input.right();
+        assert_on_boundary(&input);
+        assert_eq!(input.cursor_chars(), 1);
+        input.delete();
+        assert_eq!(input.text, "n");
+        input.delete();
+        input.right();
+        input.right();
+        assert_eq!((input.text.as_str(), input.cursor), ("n", 1));
+    }
+
+    #[test]
+    fn input_line_moves_over_every_char() {
+        let text = "a é😀 ö";
+        let mut input = typed(text);
+        for chars in (0..text.chars().count()).rev() {
+            input.left();
+            assert_on_boundary(&input);
+            assert_eq!(input.cursor_chars(), chars);
+        }
+        input.left();
+        assert_eq!(input.cursor, 0);
+        for chars in 1..=text.chars().count() {
+            input.right();
+            assert_on_boundary(&input);
+            assert_eq!(input.cursor_chars(), chars);
+        }
+    }
+
+    #[test]
+    fn input_line_deletes_multibyte_words() {
+        let mut input = typed("spawn 😀😀 ünïcode");
+        input.delete_word_back();
+        assert_eq!(input.text, "spawn 😀😀 ");
+        input.delete_word_back();
+        assert_eq!(input.text, "spawn ");
+        let mut input = typed("é😀 ünï");
+        input.home();
+        input.delete_word_forward();
+        assert_eq!((input.text.as_str(), input.cursor), (" ünï", 0));
+        input.delete_word_forward();
+        assert!(input.text.is_empty());
+    }
+
+    #[test]
+    fn input_line_keeps_to_max_len() {
+        let mut input = typed("ab");
+        assert!(input.insert('c', Some(3)));
+        assert!(!input.insert('d', Some(3)));
+        input.set("é😀xyz".into(), Some(3));
+        assert_eq!((input.text.as_str(), input.cursor), ("é😀x", "é😀x".len()));
+        input.set("short".into(), Some(10));
+        assert_eq!(input.text, "short");
+    }
+
+    #[test]
+    fn completion_and_history_respect_max_input_len() {
+        let mut app = ui_app();
+        app.world.resource_mut::<ConsoleConfig>().max_input_len = Some(6);
+        ConsolePlugin::add_cmd(&mut app, "teleport_home", || ());
+        toggle(&mut app);
+        type_text(&mut app, "telep");
+        tap(&mut app, KeyCode::Tab, Key::Tab, None);
+        assert_eq!(app.world.resource::<InputLine>().text, "telepo");
+        app.world.resource_mut::<CommandHistory>().push("echo a long line".into());
+        tap(&mut app, KeyCode::ArrowUp, Key::ArrowUp, None);
+        assert_eq!(app.world.resource::<InputLine>().text, "echo a");
+    }
+
+    #[test]
+    fn byte_index_lands_on_boundaries() {
+        let input = typed("é😀x");
+        let indices:Vec<_> = (0..5).map(|n| input.byte_index(n)).collect();
+        assert_eq!(indices, [0, 2, 6, 7, 7]);
+        assert!(indices.iter().all(|&idx| input.text.is_char_boundary(idx)));
+    }
+
+    fn ui_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, bevy::input::InputPlugin))
+            .add_event::<ReceivedCharacter>()
+            .add_event::<Ime>()
+            .add_plugins(ConsolePlugin::default().move_speed(1e9));
+        app.update();
+        app
+    }
+
+    fn key(app:&mut App, key_code:KeyCode, logical_key:Key, state:ButtonState) {
+        app.world.send_event(KeyboardInput { key_code, logical_key, state, window:Entity::PLACEHOLDER });
+    }
+
+    /// Presses and releases `key_code` over two frames, sending `char` as typed with the press.
+    fn tap(app:&mut App, key_code:KeyCode, logical_key:Key, char:Option<&str>) {
+        key(app, key_code, logical_key.clone(), ButtonState::Pressed);
+        if let Some(char) = char {
+            app.world.send_event(ReceivedCharacter { window:Entity::PLACEHOLDER, char:char.into() });
+        }
+        app.update();
+        key(app, key_code, logical_key, ButtonState::Released);
+        app.update();
+    }
+
+    fn type_text(app:&mut App, text:&str) {
+        for char in text.chars() {
+            app.world.send_event(ReceivedCharacter { window:Entity::PLACEHOLDER, char:char.to_string().into() });
+        }
+        app.update();
+    }
+
+    fn state(app:&App) -> ConsoleState {
+        *app.world.resource::<State<ConsoleState>>().get()
+    }
+
+    #[test]
+    fn submit_clears_input_and_records_history() {
+        let mut app = ui_app();
+        app.world.resource_mut::<NextState<ConsoleState>>().set(ConsoleState::AnimatingOpen);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(state(&app), ConsoleState::Open);
+        type_text(&mut app, "echo hi");
+        tap(&mut app, KeyCode::Enter, Key::Enter, Some("\r"));
+        assert!(app.world.resource::<InputLine>().text.is_empty());
+        assert_eq!(app.world.resource::<CommandHistory>().entries().last().map(String::as_str), Some("echo hi"));
+        let mut input_field = app.world.query_filtered::<&Text, With<ConsoleInputTag>>();
+        let sections:Vec<_> = input_field.single(&app.world).sections.iter().map(|section| section.value.as_str()).collect();
+        assert_eq!(sections, [" > ", "", "", "|", "", ""]);
+    }
+
+    #[test]
+    fn typing_right_after_opening_skips_the_toggle_key() {
+        let mut app = ui_app();
+        key(&mut app, KeyCode::Backquote, Key::Character("`".into()), ButtonState::Pressed);
+        app.world.send_event(ReceivedCharacter { window:Entity::PLACEHOLDER, char:"`".into() });
+        app.update();
+        // Typed while the console is still sliding open, together with the toggle key's late character.
+        type_text(&mut app, "`x");
+        key(&mut app, KeyCode::Backquote, Key::Character("`".into()), ButtonState::Released);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(state(&app), ConsoleState::Open);
+        type_text(&mut app, "help");
+        assert_eq!(app.world.resource::<InputLine>().text, "help");
+    }
+
+    fn toggle(app:&mut App) {
+        tap(app, KeyCode::Backquote, Key::Character("`".into()), Some("`"));
+        for _ in 0..3 {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn reopening_after_closing_leaves_no_backquote() {
+        let mut app = ui_app();
+        toggle(&mut app);
+        assert_eq!(state(&app), ConsoleState::Open);
+        type_text(&mut app, "ab");
+        key(&mut app, KeyCode::Backquote, Key::Character("`".into()), ButtonState::Pressed);
+        app.world.send_event(ReceivedCharacter { window:Entity::PLACEHOLDER, char:"`".into() });
+        app.update();
+        assert!(!app.world.resource::<ButtonInput<KeyCode>>().just_pressed(KeyCode::Backquote));
+        // The character may arrive a frame after the key.
+        type_text(&mut app, "`");
+        key(&mut app, KeyCode::Backquote, Key::Character("`".into()), ButtonState::Released);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(state(&app), ConsoleState::Closed);
+        toggle(&mut app);
+        assert_eq!(state(&app), ConsoleState::Open);
+        // Closing keeps the draft, but nothing of the closing or opening keypress.
+        assert_eq!(app.world.resource::<InputLine>().text, "ab");
+    }
+
+    #[test]
+    fn suggestions_count_what_doesnt_fit() {
+        let mut app = ui_app();
+        app.world.resource_mut::<ConsoleConfig>().max_suggestions = 2;
+        for name in ["zap1", "zap2", "zap3", "zap4"] {
+            ConsolePlugin::add_cmd(&mut app, name, || ());
+        }
+        toggle(&mut app);
+        type_text(&mut app, "zap");
+        let suggestions = app.world.resource::<Suggestions>();
+        assert_eq!((suggestions.names.as_slice(), suggestions.more), (&["zap1".to_owned(), "zap2".to_owned()][..], 2));
+        let mut panel = app.world.query_filtered::<&Text, With<ConsoleSuggestionsTag>>();
+        let rows:String = panel.single(&app.world).sections.iter().map(|section| section.value.as_str()).collect();
+        assert_eq!(rows, "zap1\nzap2\n+2 more");
+        tap(&mut app, KeyCode::Tab, Key::Tab, None);
+        assert_eq!(app.world.resource::<Suggestions>().selected, None);
+        let lines:Vec<_> = app.world.resource::<ConsoleOutput>().lines().collect();
+        assert_eq!(lines, ["zap1 zap2 zap3 zap4", ""]);
+    }
+
+    #[test]
+    fn profile_timing_goes_through_the_writer() {
+        let mut app = headless_app(ConsolePlugin::headless().profile(true));
+        app.world.send_event(CommandSubmitted { line:"echo hi".into() });
+        app.update();
+        let printed:Vec<_> = app.world.resource_mut::<Events<ConsolePrinted>>().drain().map(|printed| printed.text).collect();
+        assert!(printed.iter().any(|text| text.starts_with("(took ")), "{printed:?}");
+    }
+
+    #[test]
+    fn history_keeps_the_draft() {
+        let mut history = CommandHistory::default();
+        assert_eq!(history.newer(), None);
+        history.push("first".into());
+        history.push("second".into());
+        assert_eq!(history.newer(), None);
+        assert_eq!(history.older("draft"), Some("second"));
+        assert_eq!(history.older("second"), Some("first"));
+        assert_eq!(history.older("first"), None);
+        assert_eq!(history.newer(), Some("second"));
+        assert_eq!(history.newer(), Some("draft"));
+        assert_eq!(history.newer(), None);
+    }
+
+    #[test]
+    fn bind_and_alias_keep_quoting() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        ConsolePlugin::execute(&mut app, r#"bind F5 echo "a  b" c"#);
+        ConsolePlugin::execute(&mut app, r#"bind F6 "echo x; echo y""#);
+        ConsolePlugin::execute(&mut app, r#"alias say2 echo "a  b""#);
+        let bindings = app.world.resource::<KeyBindings>();
+        assert_eq!(bindings.get(&KeyCode::F5).map(String::as_str), Some(r#"echo "a  b" c"#));
+        assert_eq!(bindings.get(&KeyCode::F6).map(String::as_str), Some("echo x; echo y"));
+        assert_eq!(ConsolePlugin::execute(&mut app, "say2"), "a  b\n");
+    }
+
+    #[test]
+    fn invalid_scrollback_is_refused() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        assert_eq!(ConsolePlugin::execute(&mut app, "set scrollback 0"), "scrollback must be a positive number of lines\n");
+        app.update();
+        assert_eq!(app.world.resource::<ConsoleOutput>().max_lines(), 1000);
+        assert_eq!(ConsolePlugin::execute(&mut app, "set scrollback 5"), "scrollback = 5\n");
+        app.update();
+        assert_eq!(app.world.resource::<ConsoleOutput>().max_lines(), 5);
+    }
+
+    #[test]
+    fn alias_target_knows_the_alias() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        ConsolePlugin::add_cmd(&mut app, "give", |args:Res<CommandArgs>, mut writer:ConsoleWriter| {
+            writer.writeln(format!("{} {} {}", args.name(), args.invoked_as(), args.args().join(" ")));
+        });
+        ConsolePlugin::add_alias(&mut app, "medkit", "give health");
+        assert_eq!(ConsolePlugin::execute(&mut app, "medkit 5"), "give medkit health 5\n");
+        assert_eq!(ConsolePlugin::execute(&mut app, "give ammo"), "give give ammo\n");
+    }
+
+    #[test]
+    fn repeat_skips_aliases_of_itself() {
+        let mut app = headless_app(ConsolePlugin::headless().echo_input(false));
+        ConsolePlugin::add_alias(&mut app, "r", "!!");
+        for line in ["echo hi", "r", "r"] {
+            app.world.send_event(CommandSubmitted { line:line.into() });
+            for _ in 0..5 {
+                app.update();
+            }
+        }
+        assert!(app.world.resource::<PendingCommands>().is_empty());
+        let lines:Vec<_> = app.world.resource::<ConsoleOutput>().lines().collect();
+        assert_eq!(lines, ["hi", "Repeating: echo hi", "hi", "Repeating: echo hi", "hi", ""]);
+    }
+
+    #[test]
+    fn execute_keeps_the_output() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        assert_eq!(ConsolePlugin::execute(&mut app, "echo needle"), "needle\n");
+        assert_eq!(ConsolePlugin::execute(&mut app, "grep needle"), "1: needle\n");
+        app.world.resource_mut::<ConsoleOutput>().push_str("partial ", None);
+        assert_eq!(ConsolePlugin::execute(&mut app, "echo line"), "line\n");
+        let lines:Vec<_> = app.world.resource::<ConsoleOutput>().lines().collect();
+        assert_eq!(lines, ["needle", "1: needle", "partial line", ""]);
+        assert_eq!(ConsolePlugin::execute(&mut app, "clear; echo fresh"), "fresh\n");
+    }
+
+    #[test]
+    fn filter_rewrites_to_aliases() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        ConsolePlugin::add_alias(&mut app, "q", "echo bye");
+        ConsolePlugin::add_alias(&mut app, "loop", "loop");
+        ConsolePlugin::set_command_filter(&mut app, |name, _args| match name {
+            "quit" => Filter::Rewrite("q".into()),
+            "spin" => Filter::Rewrite("loop".into()),
+            _ => Filter::Allow,
+        });
+        assert_eq!(ConsolePlugin::execute(&mut app, "quit"), "bye\n");
+        assert_eq!(ConsolePlugin::execute(&mut app, "spin"), "Alias loop detected: loop -> loop\n");
+    }
+
+    #[test]
+    fn swapped_letters_are_one_edit() {
+        assert_eq!(edit_distance("hlep", "help"), 1);
+        assert_eq!(edit_distance("ehco", "echo"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        let names = ["help", "echo", "clear", "bind", "alias"];
+        for (typo, name) in [("hlep", "help"), ("ehco", "echo"), ("claer", "clear"), ("bnid", "bind"), ("CLAER", "clear")] {
+            assert_eq!(closest_by_edits(names.into_iter(), typo, true), Some(name), "{typo}");
+        }
+        assert_eq!(closest_by_edits(names.into_iter(), "xyzzy", true), None);
+        let mut app = headless_app(ConsolePlugin::headless());
+        for (typo, name) in [("hlep", "help"), ("ehco", "echo"), ("claer", "clear"), ("bnid", "bind")] {
+            assert_eq!(ConsolePlugin::execute(&mut app, typo), format!("Command not found: {typo}. Did you mean '{name}'?\n"));
+        }
+    }
+
+    #[test]
+    fn help_follows_aliases() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        ConsolePlugin::add_cmd_with_help(&mut app, "give", "Gives an item", || ());
+        ConsolePlugin::add_alias(&mut app, "medkit", "give health");
+        ConsolePlugin::add_alias(&mut app, "heal", "medkit 50");
+        ConsolePlugin::add_alias(&mut app, "broken", "nothing");
+        assert_eq!(ConsolePlugin::execute(&mut app, "help medkit"), "medkit -> give health\ngive\nGives an item\n");
+        assert_eq!(ConsolePlugin::execute(&mut app, "help heal"), "heal -> medkit 50\ngive\nGives an item\n");
+        assert_eq!(ConsolePlugin::execute(&mut app, "help broken"), "broken -> nothing\nNo such command: nothing\n");
+    }
+
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component, Default)]
+    struct Dummy;
+
+    #[test]
+    fn despawn_cleans_up_spawned_entities() {
+        let mut app = headless_app(ConsolePlugin::headless());
+        app.register_type::<Dummy>();
+        let bystander = app.world.spawn(Dummy).id();
+        ConsolePlugin::execute(&mut app, "set sv_cheats 1");
+        let spawned:Vec<String> = (0..3).map(|_| ConsolePlugin::execute(&mut app, "spawn_component Dummy")).collect();
+        let first = spawned[0].trim().trim_start_matches("Spawned ");
+        assert_eq!(ConsolePlugin::execute(&mut app, format!("despawn {first}")), "Despawned 1 entity\n");
+        assert_eq!(app.world.query::<&Dummy>().iter(&app.world).count(), 3);
+        assert!(ConsolePlugin::execute(&mut app, format!("despawn {bystander:?}")).contains("isn't an entity spawned by the console"));
+        assert_eq!(ConsolePlugin::execute(&mut app, "despawn"), "Despawned 2 entities\n");
+        assert_eq!(app.world.query::<&Dummy>().iter(&app.world).count(), 1);
+        assert!(app.world.get_entity(bystander).is_some());
+    }
+
+    #[test]
+    fn file_log_is_written_line_by_line() {
+        let path = std::env::temp_dir().join(format!("console_log_test_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut app = headless_app(ConsolePlugin::headless());
+        ConsolePlugin::execute(&mut app, format!("log_to_file {}", path.display()));
+        ConsolePlugin::execute(&mut app, "echo before the crash");
+        let logged = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(logged.ends_with("before the crash\n"), "{logged:?}");
+    }
+
+    #[test]
+    fn consoles_keep_their_own_commands_and_output() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, ConsolePlugin::headless(), ConsolePlugin::new("server").echo_input(false)));
+        ConsolePlugin::add_cmd(&mut app, "jump", |mut writer:ConsoleWriter| writer.writeln("jumped"));
+        ConsolePlugin::with_console(&mut app, "server", |app| {
+            ConsolePlugin::add_cmd(app, "kick", |mut writer:ConsoleWriter| writer.writeln("kicked"));
+        }).unwrap();
+        assert_eq!(app.world.resource::<ConsoleInstances>().names(), ["default", "server"]);
+        assert_eq!(ConsolePlugin::execute(&mut app, "jump"), "jumped\n");
+        assert!(ConsolePlugin::execute(&mut app, "kick").starts_with("Command not found"));
+        let server = ConsolePlugin::with_console(&mut app, "server", |app| {
+            (ConsolePlugin::execute(app, "kick"), ConsolePlugin::execute(app, "jump"), ConsolePlugin::execute(app, "help kick"))
+        }).unwrap();
+        assert_eq!(server.0, "kicked\n");
+        assert!(server.1.starts_with("Command not found"));
+        assert!(server.2.starts_with("kick\n"));
+        assert!(ConsolePlugin::with_console(&mut app, "missing", |_| ()).is_none());
+
+        // Lines queued for a console that isn't active run in the background, into its own output.
+        app.world.send_event(CommandSubmitted { line:"jump".into() });
+        ConsolePlugin::with_console(&mut app, "server", |app| app.world.resource_mut::<PendingCommands>().push_back("kick".into()));
+        app.update();
+        let lines = |app:&App| app.world.resource::<ConsoleOutput>().lines().map(str::to_owned).collect::<Vec<_>>();
+        assert_eq!(lines(&app).last_chunk::<3>().unwrap(), &["jump", "jumped", ""]);
+        assert_eq!(app.world.resource::<CommandHistory>().entries(), ["jump"]);
+        let (server, history) = ConsolePlugin::with_console(&mut app, "server", |app| (lines(app), app.world.resource::<CommandHistory>().entries().len())).unwrap();
+        assert_eq!((server.len(), server.last_chunk::<2>().unwrap(), history), (6, &["kicked".to_owned(), "".to_owned()], 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Console server is already added")]
+    fn adding_a_console_twice_panics() {
+        App::new().add_plugins((ConsolePlugin::new("server"), ConsolePlugin::new("server")));
+    }
+
+    #[test]
+    fn toggle_keys_switch_consoles() {
+        let mut app = ui_app();
+        app.add_plugins(ConsolePlugin::new("server").toggle_key(KeyCode::F2).prompt("server> ".into()).move_speed(1e9));
+        let is_server = |app:&mut App| app.world.run_system_once(console_is_active("server"));
+        let server_open = |app:&mut App| app.world.run_system_once(console_instance_is_open("server"));
+        let prompt = |app:&mut App| {
+            let mut input_field = app.world.query_filtered::<&Text, With<ConsoleInputTag>>();
+            input_field.single(&app.world).sections[INPUT_PROMPT].value.clone()
+        };
+        assert!(!is_server(&mut app));
+        tap(&mut app, KeyCode::F2, Key::F2, None);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(state(&app), ConsoleState::Open);
+        assert!(is_server(&mut app) && server_open(&mut app));
+        assert_eq!(prompt(&mut app), "server> ");
+        type_text(&mut app, "status");
+        // The default console's key swaps it in without closing.
+        toggle(&mut app);
+        assert_eq!(state(&app), ConsoleState::Open);
+        assert!(!is_server(&mut app) && !server_open(&mut app));
+        assert_eq!(prompt(&mut app), " > ");
+        assert!(app.world.resource::<InputLine>().text.is_empty());
+        assert_eq!(app.world.query_filtered::<(), With<ConsoleTag>>().iter(&app.world).count(), 1);
+        assert_eq!(*app.world.resource::<ConsoleAnimation>(), ConsoleAnimation { progress:1.0 });
+        tap(&mut app, KeyCode::F2, Key::F2, None);
+        assert_eq!(app.world.resource::<InputLine>().text, "status");
+        // Its own key closes the active console.
+        tap(&mut app, KeyCode::F2, Key::F2, None);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(state(&app), ConsoleState::Closed);
+        assert!(is_server(&mut app));
+    }
+
+    #[test]
+    fn alias_runs_every_command_of_its_target() {
+        let mut app = headless_app(ConsolePlugin::headless().echo_input(false));
+        ConsolePlugin::execute(&mut app, r#"alias both "echo a; echo b""#);
+        ConsolePlugin::execute(&mut app, r#"alias nested "both; echo c""#);
+        assert_eq!(ConsolePlugin::execute(&mut app, "both; echo c"), "a\nb\nc\n");
+        assert_eq!(ConsolePlugin::execute(&mut app, "nested"), "a\nb\nc\n");
+        app.world.resource_mut::<ConsoleOutput>().clear();
+        app.world.send_event(CommandSubmitted { line:"both; echo c".into() });
+        for _ in 0..3 {
+            app.update();
+        }
+        let lines:Vec<_> = app.world.resource::<ConsoleOutput>().lines().collect();
+        assert_eq!(lines, ["a", "b", "c", ""]);
+    }
+}